@@ -0,0 +1,158 @@
+use serde_derive::{Deserialize, Serialize};
+
+/// What differences between a typed and the expected answer are forgiven.
+/// Every level also forgives what the levels before it do.
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+pub enum Strictness {
+    /// Every character has to match.
+    Exact,
+    /// Upper and lower case letters are the same.
+    #[default]
+    IgnoreCase,
+    /// Spaces and line breaks are left out.
+    IgnoreWhitespace,
+    /// Everything but letters and digits is left out, like commas, periods or ¿ and «.
+    IgnorePunctuation,
+}
+
+/// How a character of the diff between answers is shown.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CharDiff {
+    /// Typed like in the expected answer.
+    Correct,
+    /// Typed, but not in the expected answer.
+    Extra,
+    /// In the expected answer, but not typed.
+    Missing,
+}
+
+pub struct AnswerComparison {
+    pub correct: bool,
+    /// Characters of both answers in reading order. Characters the strictness leaves out
+    /// aren't in it, neither are differences it forgives.
+    pub diff: Vec<(char, CharDiff)>,
+}
+
+/// Compares a typed answer with the expected one.
+pub fn compare_answer(expected: &str, typed: &str, strictness: Strictness) -> AnswerComparison {
+    let expected = significant_chars(expected, strictness);
+    let typed = significant_chars(typed, strictness);
+    let same = |a: char, b: char| match strictness {
+        Strictness::Exact => a == b,
+        _ => a.to_lowercase().eq(b.to_lowercase()),
+    };
+
+    // Longest common subsequence, common[i][j] is its length for typed[i..] and expected[j..]
+    let mut common = vec![vec![0; expected.len() + 1]; typed.len() + 1];
+    for i in (0..typed.len()).rev() {
+        for j in (0..expected.len()).rev() {
+            common[i][j] = if same(typed[i], expected[j]) {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut diff = Vec::new();
+    while i < typed.len() || j < expected.len() {
+        if i < typed.len() && j < expected.len() && same(typed[i], expected[j]) {
+            diff.push((typed[i], CharDiff::Correct));
+            i += 1;
+            j += 1;
+        } else if i < typed.len() && (j == expected.len() || common[i + 1][j] >= common[i][j + 1]) {
+            // Wrong characters are shown before the ones that should have been typed
+            diff.push((typed[i], CharDiff::Extra));
+            i += 1;
+        } else {
+            diff.push((expected[j], CharDiff::Missing));
+            j += 1;
+        }
+    }
+
+    AnswerComparison {
+        correct: diff
+            .iter()
+            .all(|(_, char_diff)| *char_diff == CharDiff::Correct),
+        diff,
+    }
+}
+
+/// Characters of the answer that are compared with the strictness.
+fn significant_chars(answer: &str, strictness: Strictness) -> Vec<char> {
+    let answer = match strictness {
+        // Spaces around the answer are never meant to be typed
+        Strictness::Exact | Strictness::IgnoreCase => answer.trim(),
+        _ => answer,
+    };
+    answer
+        .chars()
+        .filter(|c| match strictness {
+            Strictness::Exact | Strictness::IgnoreCase => true,
+            Strictness::IgnoreWhitespace => !c.is_whitespace(),
+            Strictness::IgnorePunctuation => c.is_alphanumeric(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marked(comparison: &AnswerComparison) -> String {
+        comparison
+            .diff
+            .iter()
+            .map(|&(c, char_diff)| match char_diff {
+                CharDiff::Correct => c.to_string(),
+                CharDiff::Extra => format!("-{}", c),
+                CharDiff::Missing => format!("+{}", c),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn exact_answers_must_match_every_character() {
+        assert!(compare_answer("Hola", "Hola", Strictness::Exact).correct);
+        assert!(compare_answer("Hola", " Hola ", Strictness::Exact).correct);
+        assert!(!compare_answer("Hola", "hola", Strictness::Exact).correct);
+    }
+
+    #[test]
+    fn ignoring_case() {
+        assert!(compare_answer("Hola", "hOLA", Strictness::IgnoreCase).correct);
+        assert!(!compare_answer("buenos días", "buenosdías", Strictness::IgnoreCase).correct);
+    }
+
+    #[test]
+    fn ignoring_whitespace() {
+        let strictness = Strictness::IgnoreWhitespace;
+        assert!(compare_answer("buenos días", "Buenos  Días", strictness).correct);
+        assert!(compare_answer("buenos días", "buenosdías", strictness).correct);
+        assert!(!compare_answer("¡Hola!", "hola", strictness).correct);
+    }
+
+    #[test]
+    fn ignoring_punctuation() {
+        let strictness = Strictness::IgnorePunctuation;
+        assert!(compare_answer("Yes, I do.", "yes i do", strictness).correct);
+        assert!(!compare_answer("Yes, I do.", "yes i don't", strictness).correct);
+        assert!(!compare_answer("¿Qué hora es?", "que hora es", strictness).correct);
+        assert!(compare_answer("¿Qué hora es?", "qué hora es", strictness).correct);
+        assert!(compare_answer("« Bonjour… »", "bonjour", strictness).correct);
+    }
+
+    #[test]
+    fn diff_marks_wrong_and_missing_characters() {
+        let comparison = compare_answer("gato", "gatto", Strictness::Exact);
+        assert!(!comparison.correct);
+        assert_eq!(marked(&comparison), "gat-to");
+
+        let comparison = compare_answer("perro", "pero", Strictness::Exact);
+        assert_eq!(marked(&comparison), "per+ro");
+
+        let comparison = compare_answer("casa", "cosa", Strictness::Exact);
+        assert_eq!(marked(&comparison), "c-o+asa");
+    }
+}
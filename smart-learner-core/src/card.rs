@@ -1,39 +1,747 @@
+use crate::date::{self, Date};
 use crate::field::Field;
 use crate::result::Result;
 use serde_derive::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
+/// Tag given to cards that were answered wrong too many times.
+pub const LEECH_TAG: &str = "leech";
+/// Ease factor of new cards.
+const DEFAULT_EASE: f64 = 2.5;
+/// SM-2 doesn't let the ease factor drop below this.
+const MIN_EASE: f64 = 1.3;
+
+/// How grades change intervals.
+pub struct IntervalModifiers {
+    /// How much the interval grows after `Result::Difficult`, used instead of the ease factor.
+    pub hard_multiplier: f64,
+    /// Extra multiplier for the interval given by `Result::Easy`. It stacks with the ease factor,
+    /// from the third review on easy answers grow the interval by `ease * easy_multiplier`.
+    pub easy_multiplier: f64,
+    /// Interval in days after `Result::Wrong`.
+    pub wrong_interval: u64,
+    /// Minutes until a side answered wrong is shown again, when there are learning steps.
+    pub again_interval_mins: u64,
+    /// Interval in days a new side gets when it graduates with `Result::Difficult`.
+    pub graduating_interval: u64,
+    /// Interval in days a new side gets when it graduates with `Result::Easy`.
+    pub easy_interval: u64,
+    /// Minutes between reviews of new and forgotten sides before they get day intervals.
+    pub learning_steps: Vec<u64>,
+}
+
+/// Direction a card is studied in.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum Side {
+    /// Front is asked, back is the answer.
+    #[default]
+    Forward,
+    /// Back is asked, front is the answer. Only bidirectional cards have it.
+    Reverse,
+}
+
+/// One of the two fields of a card.
+#[derive(Clone, Copy, PartialEq)]
+pub enum CardSide {
+    Front,
+    Back,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+pub enum CardKind {
+    /// Front is the question, back is the answer.
+    #[default]
+    Basic,
+    /// Spans of the front written in `{{...}}` are hidden in the question,
+    /// the back holds extra notes shown with the answer.
+    Cloze,
+    /// Front is the question, the answer is typed and compared with the back.
+    TypeAnswer,
+}
+
+/// Scheduling state of one side of a card.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Schedule {
+    /// Days left until the side is due.
+    pub current_repeat_in: u64,
+    /// Interval in days the side got on the last review.
+    pub repeat_in: u64,
+    #[serde(default = "default_ease")]
+    pub ease: f64,
+    /// Correct answers in a row.
+    #[serde(default)]
+    pub repetitions: u32,
+    /// Learning step the side is on, None once it graduated to day intervals.
+    #[serde(default)]
+    pub learning_step: Option<usize>,
+    /// Timestamp the current learning step ends at.
+    #[serde(default)]
+    pub learning_due: Option<i64>,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Self {
+            current_repeat_in: 0,
+            repeat_in: 0,
+            ease: DEFAULT_EASE,
+            repetitions: 0,
+            learning_step: None,
+            learning_due: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Card {
+    /// Number of the card in its deck, 0 until the deck gives it one.
+    #[serde(default)]
+    pub id: u64,
     pub front: Field,
     pub back: Field,
-    pub current_repeat_in: u64,
-    pub repeat_in: u64,
+    #[serde(default)]
+    pub kind: CardKind,
+    #[serde(flatten)]
+    pub schedule: Schedule,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Every review of the card, oldest first.
+    #[serde(default)]
+    pub history: Vec<(Date, Result)>,
+    /// The card is also studied from back to front.
+    #[serde(default)]
+    pub bidirectional: bool,
+    /// Scheduling of the back to front direction.
+    #[serde(default)]
+    pub reverse_schedule: Schedule,
+    /// Suspended cards are never served for revision.
+    #[serde(default)]
+    pub suspended: bool,
+    /// Times the card was answered wrong.
+    #[serde(default)]
+    pub lapses: u32,
+    /// Reviews that were dropped from `history` by `Deck::compact`.
+    #[serde(default)]
+    pub compacted_reviews: u32,
+    /// Cards made from the same note are siblings, they share the id even in different decks.
+    #[serde(default)]
+    pub note_id: Option<u64>,
+}
+
+fn default_ease() -> f64 {
+    DEFAULT_EASE
+}
+
+impl Schedule {
+    /// Schedules the side with the SM-2 algorithm, it's due in `repeat_in` days.
+    /// New sides and sides answered wrong go through the learning steps first,
+    /// `Result::Easy` skips the remaining steps.
+    pub fn review(&mut self, result: Result, modifiers: &IntervalModifiers) {
+        let steps = &modifiers.learning_steps;
+        let is_new = self.repetitions == 0 && self.repeat_in == 0;
+        if !steps.is_empty() && (self.learning_step.is_some() || is_new) && result != Result::Wrong
+        {
+            // New sides are on the first step before their first review
+            let next_step = self.learning_step.map_or(1, |step| step + 1);
+            if result == Result::Difficult && next_step < steps.len() {
+                self.start_learning_step(next_step, steps[next_step]);
+                return;
+            }
+
+            self.learning_step = None;
+            self.learning_due = None;
+            if self.repetitions == 0 && self.repeat_in > 0 {
+                // Relearned after a lapse, the wrong answer already set the interval
+                self.current_repeat_in = self.repeat_in;
+                return;
+            }
+        }
+
+        // SM-2 grades answers from 0 to 5
+        let quality = match result {
+            Result::Wrong => 1.0,
+            Result::Difficult => 3.0,
+            Result::Easy => 5.0,
+        };
+
+        match result {
+            Result::Wrong => {
+                self.repetitions = 0;
+                self.repeat_in = modifiers.wrong_interval;
+            }
+            Result::Difficult | Result::Easy => {
+                self.repeat_in = match (self.repetitions, result) {
+                    (0, Result::Easy) => modifiers.easy_interval.max(1),
+                    (0, _) => modifiers.graduating_interval.max(1),
+                    (1, _) => 6,
+                    _ => {
+                        let multiplier = match result {
+                            Result::Easy => self.ease * modifiers.easy_multiplier,
+                            _ => modifiers.hard_multiplier,
+                        };
+                        ((self.repeat_in as f64 * multiplier).round() as u64)
+                            .max(self.repeat_in + 1)
+                    }
+                };
+                self.repetitions += 1;
+            }
+        }
+
+        self.ease =
+            (self.ease + 0.1 - (5.0 - quality) * (0.08 + (5.0 - quality) * 0.02)).max(MIN_EASE);
+        self.current_repeat_in = self.repeat_in;
+
+        if result == Result::Wrong && !steps.is_empty() {
+            self.start_learning_step(0, modifiers.again_interval_mins);
+        }
+    }
+
+    /// Keeps the side due today, but only after `minutes` have passed.
+    fn start_learning_step(&mut self, step: usize, minutes: u64) {
+        self.learning_step = Some(step);
+        self.learning_due = Some(date::current_timestamp() + minutes as i64 * 60);
+        self.current_repeat_in = 0;
+    }
+
+    pub fn is_due(&self) -> bool {
+        self.is_due_at(date::current_timestamp())
+    }
+
+    /// Whether the side is due at `timestamp`, seconds since the Unix epoch.
+    pub fn is_due_at(&self, timestamp: i64) -> bool {
+        self.current_repeat_in == 0 && self.learning_due.map_or(true, |due| due <= timestamp)
+    }
+
+    /// Makes the side due in `days` days, 0 makes it due now.
+    /// Later reviews grow the interval from the new one.
+    pub fn reschedule(&mut self, days: u64) {
+        if days > 0 {
+            self.repeat_in = days;
+        }
+        self.current_repeat_in = days;
+        self.learning_step = None;
+        self.learning_due = None;
+    }
+
+    /// Raises intervals shorter than `min_days`.
+    /// Sides that are shown again in the same session are left as they are.
+    pub fn apply_min_interval(&mut self, min_days: u64) {
+        // Learning sides get their interval when they graduate
+        if self.learning_step.is_some() {
+            return;
+        }
+        self.repeat_in = self.repeat_in.max(min_days);
+        if self.current_repeat_in != 0 {
+            self.current_repeat_in = self.current_repeat_in.max(min_days);
+        }
+    }
 }
 
 impl Card {
     pub fn new(front: Field, back: Field) -> Self {
         Self {
+            id: 0,
             front,
             back,
-            current_repeat_in: 0,
-            repeat_in: 1,
+            kind: CardKind::default(),
+            schedule: Schedule::default(),
+            tags: Vec::new(),
+            history: Vec::new(),
+            bidirectional: false,
+            reverse_schedule: Schedule::default(),
+            suspended: false,
+            lapses: 0,
+            compacted_reviews: 0,
+            note_id: None,
         }
     }
-    pub fn review(&mut self, result: Result) {
-        match result {
-            Result::Easy => {
-                self.current_repeat_in = self.repeat_in;
-                self.repeat_in *= 2;
-            }
-            Result::Difficult => {
-                self.repeat_in *= 2;
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|card_tag| card_tag == tag)
+    }
+
+    pub fn is_leech(&self) -> bool {
+        self.has_tag(LEECH_TAG)
+    }
+
+    /// Tags and suspends the card once it was answered wrong `threshold` times,
+    /// 0 turns leech detection off. Returns true if the card just became a leech.
+    pub fn check_leech(&mut self, threshold: u32) -> bool {
+        if threshold == 0 || self.lapses < threshold || self.is_leech() {
+            return false;
+        }
+        self.tags.push(LEECH_TAG.to_string());
+        self.suspended = true;
+        true
+    }
+
+    /// Sides the card is studied in, only basic cards can be studied in reverse.
+    pub fn sides(&self) -> impl Iterator<Item = Side> {
+        let reverse = (self.bidirectional && self.kind == CardKind::Basic).then_some(Side::Reverse);
+        std::iter::once(Side::Forward).chain(reverse)
+    }
+
+    pub fn schedule(&self, side: Side) -> &Schedule {
+        match side {
+            Side::Forward => &self.schedule,
+            Side::Reverse => &self.reverse_schedule,
+        }
+    }
+
+    pub fn schedule_mut(&mut self, side: Side) -> &mut Schedule {
+        match side {
+            Side::Forward => &mut self.schedule,
+            Side::Reverse => &mut self.reverse_schedule,
+        }
+    }
+
+    /// The side that should be revised now, forward goes first if both are due.
+    pub fn due_side(&self) -> Option<Side> {
+        self.due_side_at(date::current_timestamp())
+    }
+
+    /// The side that should be revised at `timestamp`, see `Schedule::is_due_at`.
+    pub fn due_side_at(&self, timestamp: i64) -> Option<Side> {
+        self.sides()
+            .find(|&side| self.schedule(side).is_due_at(timestamp))
+    }
+
+    /// The card was never reviewed.
+    pub fn is_new(&self) -> bool {
+        self.history.is_empty() && self.compacted_reviews == 0
+    }
+
+    pub fn field_mut(&mut self, side: CardSide) -> &mut Field {
+        match side {
+            CardSide::Front => &mut self.front,
+            CardSide::Back => &mut self.back,
+        }
+    }
+
+    /// Day the card is next due on, the earliest of its sides.
+    /// Due sides are dated from the last review, so overdue cards get a day in the past.
+    pub fn next_due(&self) -> Date {
+        let today = Date::current();
+        let last_review = self.history.last().map(|(date, _)| date);
+        self.sides()
+            .map(|side| {
+                let schedule = self.schedule(side);
+                match (schedule.current_repeat_in, last_review) {
+                    (0, Some(last_review)) => {
+                        last_review.add_days(schedule.repeat_in).min(today.clone())
+                    }
+                    (days, _) => today.add_days(days),
+                }
+            })
+            .min()
+            .unwrap_or(today)
+    }
+
+    /// Field that is asked when the side is revised.
+    pub fn question_field(&self, side: Side) -> &Field {
+        match side {
+            Side::Forward => &self.front,
+            Side::Reverse => &self.back,
+        }
+    }
+
+    /// Field that is revealed as the answer when the side is revised.
+    pub fn answer_field(&self, side: Side) -> &Field {
+        match side {
+            Side::Forward => &self.back,
+            Side::Reverse => &self.front,
+        }
+    }
+
+    /// Text shown while the side is asked, cloze spans are replaced with `[...]`.
+    pub fn question(&self, side: Side) -> String {
+        match self.kind {
+            CardKind::Basic | CardKind::TypeAnswer => self.question_field(side).text.clone(),
+            CardKind::Cloze => cloze_parts(&self.front.text)
+                .into_iter()
+                .map(|(text, hidden)| if hidden { "[...]" } else { text })
+                .collect(),
+        }
+    }
+
+    /// Text shown once the answer is revealed.
+    pub fn answer(&self, side: Side) -> String {
+        self.answer_parts(side)
+            .into_iter()
+            .map(|(text, _)| text)
+            .collect()
+    }
+
+    /// Pieces of the answer, true marks pieces that were hidden in the question.
+    pub fn answer_parts(&self, side: Side) -> Vec<(String, bool)> {
+        match self.kind {
+            CardKind::Basic | CardKind::TypeAnswer => {
+                vec![(self.answer_field(side).text.clone(), false)]
             }
-            Result::Wrong => {
-                if self.repeat_in > 1 {
-                    self.repeat_in /= 2;
+            CardKind::Cloze => {
+                let mut parts: Vec<(String, bool)> = cloze_parts(&self.front.text)
+                    .into_iter()
+                    .map(|(text, hidden)| (text.to_string(), hidden))
+                    .collect();
+                if !self.back.text.is_empty() {
+                    parts.push((format!("\n{}", self.back.text), false));
                 }
-                self.current_repeat_in = self.repeat_in;
+                parts
             }
         }
     }
+
+    /// Returns a new card with the same fields and tags and a fresh schedule.
+    /// It's a sibling of the card if the card has a note id.
+    pub fn similar(&self) -> Self {
+        let mut card = Self::new(self.front.clone(), self.back.clone());
+        card.tags = self.tags.clone();
+        card.bidirectional = self.bidirectional;
+        card.note_id = self.note_id;
+        card
+    }
+
+    /// Returns the note id of the card, a new random one is given to cards without it.
+    pub fn note_id(&mut self) -> u64 {
+        *self.note_id.get_or_insert_with(rand::random)
+    }
+
+    /// The card was reviewed today.
+    pub fn reviewed_today(&self) -> bool {
+        self.history
+            .last()
+            .is_some_and(|(date, _)| *date == Date::current())
+    }
+
+    /// Recomputes the schedule by replaying `history`, for when the scheduling fields got
+    /// corrupted. The history doesn't record sides, so bidirectional cards are left as they
+    /// are, like cards whose history was compacted. Returns true if the schedule was rebuilt.
+    pub fn rebuild_schedule_from_log(
+        &mut self,
+        modifiers: &IntervalModifiers,
+        min_interval_days: u64,
+    ) -> bool {
+        if self.sides().count() > 1 || self.compacted_reviews > 0 {
+            return false;
+        }
+
+        let mut schedule = Schedule::default();
+        for (_, result) in &self.history {
+            schedule.review(*result, modifiers);
+            schedule.apply_min_interval(min_interval_days);
+        }
+        // Days since the last review count towards the interval
+        if let Some((last_review, _)) = self.history.last() {
+            let days = last_review.difference(&Date::current());
+            schedule.current_repeat_in = schedule.current_repeat_in.saturating_sub(days);
+        }
+        self.schedule = schedule;
+        self.lapses = self
+            .history
+            .iter()
+            .filter(|(_, result)| *result == Result::Wrong)
+            .count() as u32;
+        true
+    }
+
+    /// Learning step the side is on and how many steps there are, both counted from 1.
+    /// New sides are on the first step, None once the side has day intervals.
+    pub fn learning_progress(&self, side: Side, step_count: usize) -> Option<(usize, usize)> {
+        let schedule = self.schedule(side);
+        let is_new = schedule.repetitions == 0 && schedule.repeat_in == 0;
+        let step = schedule.learning_step.or(is_new.then_some(0))?;
+        // The steps may have been changed in the config since
+        (step_count > 0).then(|| ((step + 1).min(step_count), step_count))
+    }
+
+    /// Forgets the reviews and scheduling of the card, it's new again.
+    /// Fields, tags and media stay.
+    pub fn reset_progress(&mut self) {
+        self.schedule = Schedule::default();
+        self.reverse_schedule = Schedule::default();
+        self.history.clear();
+        self.suspended = false;
+        self.lapses = 0;
+        self.compacted_reviews = 0;
+    }
+
+    /// Schedules one side of the card, the other side isn't changed.
+    pub fn review(&mut self, side: Side, result: Result, modifiers: &IntervalModifiers) {
+        self.schedule_mut(side).review(result, modifiers);
+        self.history.push((Date::current(), result));
+        if result == Result::Wrong {
+            self.lapses += 1;
+        }
+    }
+}
+
+/// Splits cloze text into pieces, true marks the ones written in `{{...}}`.
+fn cloze_parts(text: &str) -> Vec<(&str, bool)> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(length) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        if start > 0 {
+            parts.push((&rest[..start], false));
+        }
+        parts.push((&rest[start + 2..start + 2 + length], true));
+        rest = &rest[start + 2 + length + 2..];
+    }
+    if !rest.is_empty() {
+        parts.push((rest, false));
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modifiers(easy_multiplier: f64) -> IntervalModifiers {
+        IntervalModifiers {
+            hard_multiplier: 1.2,
+            easy_multiplier,
+            wrong_interval: 1,
+            again_interval_mins: 1,
+            graduating_interval: 1,
+            easy_interval: 1,
+            learning_steps: Vec::new(),
+        }
+    }
+
+    fn card(text: &str) -> Card {
+        let field = Field {
+            text: text.to_string(),
+            audio_path: None,
+            image_path: None,
+            image_after_answer: false,
+        };
+        Card::new(field.clone(), field)
+    }
+
+    fn intervals(results: &[Result], modifiers: &IntervalModifiers) -> Vec<u64> {
+        let mut schedule = Schedule::default();
+        results
+            .iter()
+            .map(|&result| {
+                schedule.review(result, modifiers);
+                schedule.repeat_in
+            })
+            .collect()
+    }
+
+    #[test]
+    fn easy_answers_stack_ease_and_easy_multiplier() {
+        // The ease factor is 2.7 after two easy answers, 6 * 2.7 * 1.3 rounds to 21
+        assert_eq!(
+            intervals(&[Result::Easy; 3], &modifiers(1.3)),
+            vec![1, 6, 21]
+        );
+    }
+
+    #[test]
+    fn easy_answers_without_multiplier_follow_ease_factor() {
+        assert_eq!(
+            intervals(&[Result::Easy; 3], &modifiers(1.0)),
+            vec![1, 6, 16]
+        );
+    }
+
+    #[test]
+    fn graduating_difficult_uses_graduating_interval() {
+        let modifiers = IntervalModifiers {
+            graduating_interval: 3,
+            easy_interval: 5,
+            learning_steps: vec![1, 10],
+            ..modifiers(1.3)
+        };
+        let mut schedule = Schedule::default();
+        schedule.review(Result::Difficult, &modifiers);
+        assert_eq!(schedule.learning_step, Some(1));
+        schedule.review(Result::Difficult, &modifiers);
+        assert_eq!(schedule.learning_step, None);
+        assert_eq!(schedule.repeat_in, 3);
+    }
+
+    #[test]
+    fn graduating_easy_uses_easy_interval() {
+        let modifiers = IntervalModifiers {
+            graduating_interval: 3,
+            easy_interval: 5,
+            learning_steps: vec![1, 10],
+            ..modifiers(1.3)
+        };
+        let mut schedule = Schedule::default();
+        schedule.review(Result::Easy, &modifiers);
+        assert_eq!(schedule.learning_step, None);
+        assert_eq!(schedule.repeat_in, 5);
+        assert_eq!(schedule.current_repeat_in, 5);
+    }
+
+    #[test]
+    fn wrong_side_is_due_after_again_interval() {
+        let modifiers = IntervalModifiers {
+            again_interval_mins: 5,
+            learning_steps: vec![1, 10],
+            ..modifiers(1.3)
+        };
+        let mut schedule = Schedule {
+            repeat_in: 10,
+            current_repeat_in: 0,
+            repetitions: 3,
+            ..Schedule::default()
+        };
+        let before = date::current_timestamp();
+        schedule.review(Result::Wrong, &modifiers);
+        let after = date::current_timestamp();
+
+        assert_eq!(schedule.learning_step, Some(0));
+        assert!(!schedule.is_due_at(before + 5 * 60 - 1));
+        assert!(schedule.is_due_at(after + 5 * 60));
+    }
+
+    #[test]
+    fn rebuilt_schedule_matches_live_reviews() {
+        let modifiers = IntervalModifiers {
+            learning_steps: vec![1, 10],
+            ..modifiers(1.3)
+        };
+        let mut card = card("front");
+        let results = [
+            Result::Difficult,
+            Result::Easy,
+            Result::Easy,
+            Result::Wrong,
+            Result::Easy,
+            Result::Difficult,
+        ];
+        for result in results {
+            card.review(Side::Forward, result, &modifiers);
+            card.schedule.apply_min_interval(1);
+        }
+
+        let mut rebuilt = card.clone();
+        rebuilt.schedule = Schedule {
+            repeat_in: 999,
+            ease: 9.0,
+            ..Schedule::default()
+        };
+        rebuilt.lapses = 0;
+        assert!(rebuilt.rebuild_schedule_from_log(&modifiers, 1));
+
+        assert_eq!(rebuilt.schedule.repeat_in, card.schedule.repeat_in);
+        assert_eq!(
+            rebuilt.schedule.current_repeat_in,
+            card.schedule.current_repeat_in
+        );
+        assert_eq!(rebuilt.schedule.ease, card.schedule.ease);
+        assert_eq!(rebuilt.schedule.repetitions, card.schedule.repetitions);
+        assert_eq!(rebuilt.schedule.learning_step, card.schedule.learning_step);
+        assert_eq!(rebuilt.lapses, card.lapses);
+    }
+
+    #[test]
+    fn bidirectional_card_is_not_rebuilt() {
+        let mut card = card("front");
+        card.bidirectional = true;
+        assert!(!card.rebuild_schedule_from_log(&modifiers(1.3), 1));
+    }
+
+    #[test]
+    fn learning_progress_follows_steps() {
+        let modifiers = IntervalModifiers {
+            learning_steps: vec![1, 10, 60],
+            ..modifiers(1.3)
+        };
+        let mut card = card("front");
+        assert_eq!(card.learning_progress(Side::Forward, 3), Some((1, 3)));
+        card.review(Side::Forward, Result::Difficult, &modifiers);
+        assert_eq!(card.learning_progress(Side::Forward, 3), Some((2, 3)));
+        card.review(Side::Forward, Result::Difficult, &modifiers);
+        assert_eq!(card.learning_progress(Side::Forward, 3), Some((3, 3)));
+        card.review(Side::Forward, Result::Difficult, &modifiers);
+        assert_eq!(card.learning_progress(Side::Forward, 3), None);
+    }
+
+    #[test]
+    fn learning_progress_without_steps_is_none() {
+        assert_eq!(card("front").learning_progress(Side::Forward, 0), None);
+    }
+
+    #[test]
+    fn short_intervals_are_raised_to_minimum() {
+        let mut schedule = Schedule::default();
+        schedule.review(Result::Easy, &modifiers(1.3));
+        assert_eq!(schedule.repeat_in, 1);
+
+        schedule.apply_min_interval(3);
+        assert_eq!(schedule.repeat_in, 3);
+        assert_eq!(schedule.current_repeat_in, 3);
+    }
+
+    #[test]
+    fn learning_steps_are_exempt_from_minimum_interval() {
+        let modifiers = IntervalModifiers {
+            learning_steps: vec![1, 10],
+            ..modifiers(1.3)
+        };
+        let mut schedule = Schedule::default();
+        schedule.review(Result::Wrong, &modifiers);
+
+        schedule.apply_min_interval(3);
+        assert_eq!(schedule.learning_step, Some(0));
+        assert_eq!(schedule.current_repeat_in, 0);
+        assert_eq!(schedule.repeat_in, 1);
+    }
+
+    #[test]
+    fn basic_card_asks_front_and_answers_back() {
+        let mut card = card("cat");
+        card.back.text = "gato".to_string();
+        assert_eq!(card.question(Side::Forward), "cat");
+        assert_eq!(card.answer(Side::Forward), "gato");
+    }
+
+    #[test]
+    fn reverse_side_asks_back_and_answers_front() {
+        let mut card = card("cat");
+        card.back.text = "gato".to_string();
+        card.bidirectional = true;
+        assert_eq!(card.question(Side::Reverse), "gato");
+        assert_eq!(card.answer(Side::Reverse), "cat");
+    }
+
+    #[test]
+    fn cloze_card_hides_spans_and_adds_notes_to_answer() {
+        let mut card = card("The {{cell}} is the unit of {{life}}");
+        card.back.text = "Biology".to_string();
+        card.kind = CardKind::Cloze;
+        assert_eq!(
+            card.question(Side::Forward),
+            "The [...] is the unit of [...]"
+        );
+        assert_eq!(
+            card.answer(Side::Forward),
+            "The cell is the unit of life\nBiology"
+        );
+    }
+
+    #[test]
+    fn easy_multiplier_scales_mature_interval_proportionally() {
+        let easy_interval = |easy_multiplier| {
+            let mut schedule = Schedule {
+                current_repeat_in: 0,
+                repeat_in: 20,
+                ease: 2.5,
+                repetitions: 5,
+                ..Schedule::default()
+            };
+            schedule.review(Result::Easy, &modifiers(easy_multiplier));
+            schedule.repeat_in
+        };
+        assert_eq!(easy_interval(1.0), 50);
+        assert_eq!(easy_interval(1.3), 65);
+        assert_eq!(easy_interval(2.0), 2 * easy_interval(1.0));
+    }
 }
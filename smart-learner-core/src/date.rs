@@ -1,11 +1,18 @@
-use chrono::{self, Datelike};
+use chrono::{self, Datelike, Timelike};
 use serde_derive::{Deserialize, Serialize};
+use std::fmt;
 
-#[derive(Serialize, Deserialize)]
+/// A day with an optional time of day, dates saved without a time are at midnight.
+/// Comparisons only look at the day.
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Date {
     pub day: u8,
     pub month: u8,
     pub year: u16,
+    #[serde(default)]
+    pub hour: u8,
+    #[serde(default)]
+    pub minute: u8,
 }
 
 impl PartialEq for Date {
@@ -14,17 +21,24 @@ impl PartialEq for Date {
     }
 }
 
+/// Formats the day as YYYY-MM-DD.
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl Eq for Date {}
+
 impl PartialOrd for Date {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        match self.year.partial_cmp(&other.year) {
-            Some(core::cmp::Ordering::Equal) => {}
-            ord => return ord,
-        }
-        match self.month.partial_cmp(&other.month) {
-            Some(core::cmp::Ordering::Equal) => {}
-            ord => return ord,
-        }
-        self.day.partial_cmp(&other.day)
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Date {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.year, self.month, self.day).cmp(&(other.year, other.month, other.day))
     }
 }
 
@@ -37,35 +51,78 @@ impl Date {
             day: date.day() as u8,
             month: date.month() as u8,
             year: date.year() as u16,
+            hour: date.hour() as u8,
+            minute: date.minute() as u8,
         }
     }
 
-    /// Returns difference between 2 dates in days.
-    pub fn difference(&self, other: &Self) -> u64 {
-        let mut difference: u64 = 0;
-
-        for year in self.year..other.year {
-            if is_leap_year(&year) {
-                difference += 366;
-            } else {
-                difference += 365;
-            }
-        }
+    /// Returns the date that comes `days` days after this one.
+    pub fn add_days(&self, days: u64) -> Self {
+        let date = self
+            .naive()
+            .checked_add_days(chrono::Days::new(days))
+            .unwrap();
 
-        for month in self.month..other.month {
-            difference += month_length(&month, &self.year) as u64
+        Date {
+            day: date.day() as u8,
+            month: date.month() as u8,
+            year: date.year() as u16,
+            hour: self.hour,
+            minute: self.minute,
         }
+    }
+
+    /// Returns the date that comes `days` days before this one.
+    pub fn sub_days(&self, days: u64) -> Self {
+        let date = self
+            .naive()
+            .checked_sub_days(chrono::Days::new(days))
+            .unwrap();
 
-        if self.day > other.day {
-            difference += (self.day - other.day) as u64;
-        } else if other.day > self.day {
-            difference += (other.day - self.day) as u64;
+        Date {
+            day: date.day() as u8,
+            month: date.month() as u8,
+            year: date.year() as u16,
+            hour: self.hour,
+            minute: self.minute,
         }
+    }
+
+    /// Returns difference between 2 dates in days, no matter which one comes first.
+    pub fn difference(&self, other: &Self) -> u64 {
+        other
+            .naive()
+            .signed_duration_since(self.naive())
+            .num_days()
+            .unsigned_abs()
+    }
 
-        difference
+    /// Returns difference between 2 dates in whole hours, no matter which one comes first.
+    pub fn hours_difference(&self, other: &Self) -> u64 {
+        other
+            .naive_date_time()
+            .signed_duration_since(self.naive_date_time())
+            .num_hours()
+            .unsigned_abs()
+    }
+
+    fn naive_date_time(&self) -> chrono::NaiveDateTime {
+        self.naive()
+            .and_hms_opt(self.hour as u32, self.minute as u32, 0)
+            .unwrap()
+    }
+
+    fn naive(&self) -> chrono::NaiveDate {
+        chrono::NaiveDate::from_ymd_opt(self.year as i32, self.month as u32, self.day as u32)
+            .unwrap()
     }
 }
 
+/// Seconds since the Unix epoch, for scheduling finer than a day.
+pub fn current_timestamp() -> i64 {
+    chrono::offset::Local::now().timestamp()
+}
+
 const DAYS_IN_MONTH: [u8; 12] = [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
 pub fn month_length(month: &u8, year: &u16) -> u8 {
     if is_leap_year(year) && *month == 2 {
@@ -78,3 +135,29 @@ pub fn month_length(month: &u8, year: &u16) -> u8 {
 pub fn is_leap_year(year: &u16) -> bool {
     ((year % 4 == 0) && (year % 100 != 0)) || (year % 400 == 0)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: u16, month: u8, day: u8) -> Date {
+        Date { day, month, year, hour: 0, minute: 0 }
+    }
+
+    #[test]
+    fn days_cross_year_boundary() {
+        let new_year = date(2023, 12, 31).add_days(1);
+        assert!(new_year == date(2024, 1, 1));
+        assert!(new_year.sub_days(1) == date(2023, 12, 31));
+        assert_eq!(date(2023, 12, 31).difference(&date(2024, 1, 1)), 1);
+    }
+
+    #[test]
+    fn days_include_leap_day() {
+        assert!(date(2024, 2, 28).add_days(1) == date(2024, 2, 29));
+        assert!(date(2024, 2, 28).add_days(2) == date(2024, 3, 1));
+        assert_eq!(date(2024, 2, 1).difference(&date(2024, 3, 1)), 29);
+        assert_eq!(date(2023, 2, 1).difference(&date(2023, 3, 1)), 28);
+        assert_eq!(month_length(&2, &2024), 29);
+    }
+}
@@ -1,12 +1,104 @@
-use crate::card::Card;
-use crate::date::Date;
+use crate::card::{Card, CardSide, IntervalModifiers};
+use crate::date::{self, Date};
+use crate::result::Result;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use serde_derive::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 
-#[derive(Serialize, Deserialize)]
+pub enum DeckStatus {
+    /// The deck doesn't have any cards.
+    NoCards,
+    /// There are cards to review right now.
+    HasDue,
+    /// Some cards are due, but the daily limits are used up.
+    LimitReached,
+    /// Every card was reviewed, `next_due` is the date the next one is due.
+    AllDoneToday { next_due: Option<Date> },
+}
+
+/// How many cards are served per day, 0 means no limit.
+#[derive(Default)]
+pub struct DailyLimits {
+    /// Cards that were never reviewed.
+    pub new_cards: u32,
+    pub reviews: u32,
+    /// New cards that can still be served today in all decks together, None if they aren't shared.
+    pub new_budget: Option<usize>,
+    /// Notes with a card reviewed today, their new cards are buried until tomorrow.
+    pub buried_notes: HashSet<u64>,
+}
+
+/// Order due cards are served in.
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+pub enum ReviewOrder {
+    /// Cards that are due for the longest time go first.
+    #[default]
+    MostOverdue,
+    /// Shuffled, the same seed always gives the same order.
+    Random,
+    /// Oldest cards go first.
+    Added,
+}
+
+pub struct DeckStats {
+    pub due_today: usize,
+    /// Reviews of all time.
+    pub total_reviews: usize,
+    /// Answers in the last days `Deck::stats` was asked about.
+    pub wrong: usize,
+    pub difficult: usize,
+    pub easy: usize,
+}
+
+/// Problems of a deck found by `Deck::health_report`, cards are given by index.
+#[derive(Default)]
+pub struct HealthReport {
+    /// Cards that were answered wrong too many times.
+    pub leeches: Vec<usize>,
+    /// Media files used by cards that are missing from the folder.
+    pub broken_media: Vec<BrokenMedia>,
+    /// Pairs of cards with the same front, see `Deck::find_duplicates`.
+    pub duplicates: Vec<(usize, usize)>,
+    /// Cards without text on either side.
+    pub empty_cards: Vec<usize>,
+}
+
+pub struct BrokenMedia {
+    pub card: usize,
+    pub side: CardSide,
+    pub file_name: String,
+}
+
+impl HealthReport {
+    pub fn is_healthy(&self) -> bool {
+        self.leeches.is_empty()
+            && self.broken_media.is_empty()
+            && self.duplicates.is_empty()
+            && self.empty_cards.is_empty()
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+pub enum TextDirection {
+    #[default]
+    Ltr,
+    Rtl,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Deck {
     pub name: String,
     pub cards: Vec<Card>,
     pub last_update: Date, // day, month, year
+    #[serde(default)]
+    pub text_direction: TextDirection,
+    /// Notes about the deck, like where the cards come from.
+    #[serde(default)]
+    pub description: String,
+    /// Highest card id given out, ids of deleted cards aren't reused.
+    #[serde(default)]
+    last_card_id: u64,
 }
 
 impl Deck {
@@ -15,6 +107,9 @@ impl Deck {
             name,
             cards: Vec::new(),
             last_update: Date::current(),
+            text_direction: TextDirection::default(),
+            description: String::new(),
+            last_card_id: 0,
         }
     }
     pub fn update(&mut self) {
@@ -25,40 +120,632 @@ impl Deck {
 
         let days_since_last_update = self.last_update.difference(&current_date);
 
-        for card_index in 0..self.cards.len() {
-            if self.cards[card_index].current_repeat_in > days_since_last_update {
-                self.cards[card_index].current_repeat_in -= days_since_last_update;
-            } else {
-                self.cards[card_index].current_repeat_in = 0;
+        for card in &mut self.cards {
+            for schedule in [&mut card.schedule, &mut card.reverse_schedule] {
+                if schedule.current_repeat_in > days_since_last_update {
+                    schedule.current_repeat_in -= days_since_last_update;
+                } else {
+                    schedule.current_repeat_in = 0;
+                }
             }
         }
 
         self.last_update = current_date;
     }
 
-    pub fn due_card(&self) -> Option<usize> {
-        let mut result = None;
-        for card in self.cards.iter().enumerate() {
-            if card.1.current_repeat_in == 0 {
-                result = Some(card.0);
+    /// Appends the card with a new id, returns its index.
+    pub fn add_card(&mut self, mut card: Card) -> usize {
+        self.last_card_id += 1;
+        card.id = self.last_card_id;
+        self.cards.push(card);
+        self.cards.len() - 1
+    }
+
+    /// Appends cards that have some text on either side, returns how many were added.
+    /// The cards get new ids, so ids from other decks don't collide.
+    pub fn import_cards(&mut self, cards: Vec<Card>) -> usize {
+        let cards_before = self.cards.len();
+        for card in cards {
+            if !card.front.text.trim().is_empty() || !card.back.text.trim().is_empty() {
+                self.add_card(card);
             }
         }
-        result
+        self.cards.len() - cards_before
+    }
+
+    /// Gives ids to cards that don't have one or share it with an earlier card,
+    /// like cards of decks saved before there were ids.
+    pub fn assign_card_ids(&mut self) {
+        let highest_id = self.cards.iter().map(|card| card.id).max().unwrap_or(0);
+        self.last_card_id = self.last_card_id.max(highest_id);
+        let mut used_ids = HashSet::new();
+        for card in &mut self.cards {
+            if card.id == 0 || !used_ids.insert(card.id) {
+                self.last_card_id += 1;
+                card.id = self.last_card_id;
+                used_ids.insert(card.id);
+            }
+        }
+    }
+
+    /// Finds the card with the id, together with its index.
+    pub fn card_by_id(&self, id: u64) -> Option<(usize, &Card)> {
+        self.cards
+            .iter()
+            .enumerate()
+            .find(|(_, card)| card.id == id)
+    }
+
+    /// Deletes a card, the cards after it keep their order and move one index down.
+    pub fn remove_card(&mut self, card_index: usize) -> Card {
+        self.cards.remove(card_index)
+    }
+
+    /// Iterates over cards that aren't suspended, together with their indices.
+    pub fn iter_active(&self) -> impl DoubleEndedIterator<Item = (usize, &Card)> {
+        self.cards
+            .iter()
+            .enumerate()
+            .filter(|(_, card)| !card.suspended)
+    }
+
+    /// Iterates over cards that are due at `timestamp`, together with their indices.
+    pub fn iter_due(&self, timestamp: i64) -> impl DoubleEndedIterator<Item = (usize, &Card)> {
+        self.iter_active()
+            .filter(move |(_, card)| card.due_side_at(timestamp).is_some())
+    }
+
+    /// Iterates over cards that were never reviewed, together with their indices.
+    pub fn iter_new(&self) -> impl DoubleEndedIterator<Item = (usize, &Card)> {
+        self.cards
+            .iter()
+            .enumerate()
+            .filter(|(_, card)| card.is_new())
+    }
+
+    /// Iterates over suspended cards, together with their indices.
+    pub fn iter_suspended(&self) -> impl DoubleEndedIterator<Item = (usize, &Card)> {
+        self.cards
+            .iter()
+            .enumerate()
+            .filter(|(_, card)| card.suspended)
+    }
+
+    pub fn due_count(&self) -> usize {
+        self.iter_due(date::current_timestamp()).count()
+    }
+
+    /// Indices of due cards in the order they are served for revision.
+    fn revision_order(&self, order: ReviewOrder, seed: u64) -> Vec<usize> {
+        let mut due: Vec<usize> = self
+            .iter_due(date::current_timestamp())
+            .map(|(card_index, _)| card_index)
+            .collect();
+        match order {
+            ReviewOrder::MostOverdue => {
+                due.sort_by_key(|&card_index| self.cards[card_index].next_due())
+            }
+            // Every card gets its own key, so reviewing a card doesn't shuffle the rest again
+            ReviewOrder::Random => due.sort_by_cached_key(|&card_index| {
+                StdRng::seed_from_u64(seed ^ card_index as u64).gen::<u64>()
+            }),
+            ReviewOrder::Added => {}
+        }
+        due
+    }
+
+    pub fn due_card(&self, limits: &DailyLimits, order: ReviewOrder, seed: u64) -> Option<usize> {
+        self.due_card_with_tag(None, limits, order, seed)
+    }
+
+    /// Like `due_card`, but only serves cards with the tag if one is given.
+    pub fn due_card_with_tag(
+        &self,
+        tag: Option<&str>,
+        limits: &DailyLimits,
+        order: ReviewOrder,
+        seed: u64,
+    ) -> Option<usize> {
+        self.serving_order(tag, limits, order, seed).first().copied()
+    }
+
+    /// Due cards in the order they are served, as far as the daily limits allow.
+    /// Every card counts towards the limits for the cards after it.
+    fn serving_order(
+        &self,
+        tag: Option<&str>,
+        limits: &DailyLimits,
+        order: ReviewOrder,
+        seed: u64,
+    ) -> Vec<usize> {
+        let (new_cards, reviews) = self.reviewed_today();
+        // None means no limit
+        let remaining =
+            |limit: u32, done: u32| (limit != 0).then(|| limit.saturating_sub(done) as usize);
+        let mut new_left = [remaining(limits.new_cards, new_cards), limits.new_budget]
+            .into_iter()
+            .flatten()
+            .min();
+        let mut reviews_left = remaining(limits.reviews, reviews);
+
+        self.revision_order(order, seed)
+            .into_iter()
+            .filter(|&card_index| {
+                let card = &self.cards[card_index];
+                if !tag.map_or(true, |tag| card.has_tag(tag)) {
+                    return false;
+                }
+                let buried = card.note_id.is_some_and(|note| limits.buried_notes.contains(&note));
+                if card.is_new() && buried {
+                    return false;
+                }
+                let left = if card.is_new() { &mut new_left } else { &mut reviews_left };
+                match left {
+                    Some(0) => false,
+                    Some(count) => {
+                        *count -= 1;
+                        true
+                    }
+                    None => true,
+                }
+            })
+            .collect()
+    }
+
+    /// Note ids of cards reviewed today, see `DailyLimits::buried_notes`.
+    pub fn notes_reviewed_today(&self) -> impl Iterator<Item = u64> + '_ {
+        self.cards
+            .iter()
+            .filter(|card| card.reviewed_today())
+            .filter_map(|card| card.note_id)
+    }
+
+    /// Counts cards first reviewed today and today's reviews of older cards.
+    pub fn reviewed_today(&self) -> (u32, u32) {
+        let today = Date::current();
+        let mut new_cards = 0;
+        let mut reviews = 0;
+        for card in &self.cards {
+            match card.history.first() {
+                Some((first_review, _)) if *first_review == today && card.compacted_reviews == 0 => {
+                    new_cards += 1
+                }
+                _ => {
+                    reviews += card
+                        .history
+                        .iter()
+                        .filter(|(date, _)| *date == today)
+                        .count() as u32
+                }
+            }
+        }
+        (new_cards, reviews)
+    }
+
+    /// Returns up to `n` next cards `due_card_with_tag` would serve, without changing anything.
+    pub fn upcoming(
+        &self,
+        n: usize,
+        tag: Option<&str>,
+        limits: &DailyLimits,
+        order: ReviewOrder,
+        seed: u64,
+    ) -> Vec<usize> {
+        let mut cards = self.serving_order(tag, limits, order, seed);
+        cards.truncate(n);
+        cards
+    }
+
+    pub fn status(&self, limits: &DailyLimits) -> DeckStatus {
+        if self.cards.is_empty() {
+            DeckStatus::NoCards
+        } else if self.due_card(limits, ReviewOrder::Added, 0).is_some() {
+            DeckStatus::HasDue
+        } else if self.iter_due(date::current_timestamp()).next().is_some() {
+            DeckStatus::LimitReached
+        } else {
+            let next_due = self
+                .iter_active()
+                .flat_map(|(_, card)| {
+                    card.sides().map(|side| card.schedule(side).current_repeat_in)
+                })
+                .min()
+                .map(|days| self.last_update.add_days(days));
+            DeckStatus::AllDoneToday { next_due }
+        }
+    }
+
+    /// Counts due cards and reviews, answers are only counted for the last `days` days.
+    pub fn stats(&self, days: u64) -> DeckStats {
+        let today = Date::current();
+        let mut stats = DeckStats {
+            due_today: self.due_count(),
+            total_reviews: 0,
+            wrong: 0,
+            difficult: 0,
+            easy: 0,
+        };
+
+        for card in &self.cards {
+            stats.total_reviews += card.compacted_reviews as usize;
+        }
+        for (date, result) in self.cards.iter().flat_map(|card| &card.history) {
+            stats.total_reviews += 1;
+            if *date > today || date.difference(&today) >= days {
+                continue;
+            }
+            match result {
+                Result::Wrong => stats.wrong += 1,
+                Result::Difficult => stats.difficult += 1,
+                Result::Easy => stats.easy += 1,
+            }
+        }
+        stats
+    }
+
+    /// Drops reviews older than `keep_log_days` days to keep the deck file small.
+    /// The last review of every card stays, it tells the card isn't new and when it was seen.
+    /// Dropped reviews still count in `DeckStats::total_reviews`. Returns how many were dropped.
+    pub fn compact(&mut self, keep_log_days: u32) -> usize {
+        let cutoff = Date::current().sub_days(keep_log_days as u64);
+        let mut dropped = 0;
+        for card in &mut self.cards {
+            let last = card.history.len().saturating_sub(1);
+            let old = card.history[..last]
+                .iter()
+                .take_while(|(date, _)| *date < cutoff)
+                .count();
+            card.history.drain(..old);
+            card.compacted_reviews += old as u32;
+            dropped += old;
+        }
+        dropped
     }
 
+    /// Rebuilds the schedules of all cards from their history, see
+    /// `Card::rebuild_schedule_from_log`. Returns how many cards were rebuilt.
+    pub fn rebuild_schedules(
+        &mut self,
+        modifiers: &IntervalModifiers,
+        min_interval_days: u64,
+    ) -> usize {
+        self.cards
+            .iter_mut()
+            .filter(|card| !card.is_new())
+            .map(|card| card.rebuild_schedule_from_log(modifiers, min_interval_days))
+            .filter(|&rebuilt| rebuilt)
+            .count()
+    }
+
+    /// Collects leeches, missing media, duplicates and empty cards.
+    /// `folder` is the folder with decks, media is looked up in its `audio` and `images` folders.
+    pub fn health_report(&self, folder: &Path) -> HealthReport {
+        let mut report = HealthReport {
+            duplicates: self.find_duplicates(),
+            ..HealthReport::default()
+        };
+        for (card_index, card) in self.cards.iter().enumerate() {
+            if card.is_leech() {
+                report.leeches.push(card_index);
+            }
+            if card.front.text.trim().is_empty() && card.back.text.trim().is_empty() {
+                report.empty_cards.push(card_index);
+            }
+            for (side, field) in [(CardSide::Front, &card.front), (CardSide::Back, &card.back)] {
+                let media = [("audio", &field.audio_path), ("images", &field.image_path)];
+                for (media_folder, file_name) in media {
+                    let Some(file_name) = file_name else {
+                        continue;
+                    };
+                    // Decks from older versions may have full paths to audio, join keeps them
+                    if !folder.join(media_folder).join(file_name).is_file() {
+                        report.broken_media.push(BrokenMedia {
+                            card: card_index,
+                            side,
+                            file_name: file_name.clone(),
+                        });
+                    }
+                }
+            }
+        }
+        report
+    }
+
+    /// Finds cards with the text on the searched side or in one of their tags, ignoring case.
+    /// Cards where the text is found closer to the start come first, tag matches go last.
     pub fn search(&self, back_search: bool, search_text: String) -> Vec<(usize, String)>{
+        let query = search_text.trim().to_lowercase();
         let mut result = Vec::new();
         for (card_index, card) in self.cards.iter().enumerate() {
-            if back_search {
-                if card.back.text.contains(&search_text) {
-                    result.push((card_index, card.back.text.clone()))
+            let field = if back_search { &card.back } else { &card.front };
+            let position = field.text.to_lowercase().find(&query).or_else(|| {
+                card.tags
+                    .iter()
+                    .any(|tag| tag.to_lowercase().contains(&query))
+                    .then_some(usize::MAX)
+            });
+            if let Some(position) = position {
+                result.push((position, card_index, field.text.clone()));
+            }
+        }
+
+        result.sort_by_key(|&(position, _, _)| position);
+        result
+            .into_iter()
+            .map(|(_, card_index, text)| (card_index, text))
+            .collect()
+    }
+
+    /// Pairs of cards with the same front, ignoring case and surrounding whitespace.
+    /// Every later copy is paired with the first card that has the front.
+    pub fn find_duplicates(&self) -> Vec<(usize, usize)> {
+        let mut first_cards: HashMap<String, usize> = HashMap::new();
+        let mut duplicates = Vec::new();
+        for (card_index, card) in self.cards.iter().enumerate() {
+            let front = card.front.text.trim().to_lowercase();
+            match first_cards.get(&front) {
+                Some(&first) => duplicates.push((first, card_index)),
+                None => {
+                    first_cards.insert(front, card_index);
                 }
+            }
+        }
+        duplicates
+    }
+
+    /// Finds cards where the searched side is close to the text, allowing a few typos.
+    /// Returns (card, text, score), score is the number of typos and lower scores come first.
+    pub fn fuzzy_search(
+        &self,
+        back_search: bool,
+        search_text: String,
+    ) -> Vec<(usize, String, usize)> {
+        let query_text = search_text.trim().to_lowercase();
+        let query: Vec<char> = query_text.chars().collect();
+        let max_typos = (query.len() + 1) / 4;
+
+        let mut result = Vec::new();
+        for (card_index, card) in self.cards.iter().enumerate() {
+            let field = if back_search { &card.back } else { &card.front };
+            let text = field.text.to_lowercase();
+            let score = if text.contains(&query_text) {
+                0
             } else {
-                if card.front.text.contains(&search_text) {
-                    result.push((card_index, card.front.text.clone()))
-                }
+                text.split_whitespace()
+                    .chain([text.as_str()])
+                    .map(|word| edit_distance(&query, &word.chars().collect::<Vec<char>>()))
+                    .min()
+                    .unwrap_or(query.len())
+            };
+            if score <= max_typos {
+                result.push((card_index, field.text.clone(), score));
             }
         }
+
+        result.sort_by_key(|&(_, _, score)| score);
         result
     }
 }
+
+/// Levenshtein distance: how many characters have to be inserted, removed or replaced.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let replace = previous[j] + usize::from(a_char != b_char);
+            current.push(replace.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::Field;
+
+    fn card(text: &str) -> Card {
+        let field = Field {
+            text: text.to_string(),
+            audio_path: None,
+            image_path: None,
+            image_after_answer: false,
+        };
+        Card::new(field.clone(), field)
+    }
+
+    fn deck(texts: &[&str]) -> Deck {
+        let mut deck = Deck::new("Test".to_string());
+        deck.cards = texts.iter().map(|text| card(text)).collect();
+        deck
+    }
+
+    fn fronts(deck: &Deck) -> Vec<&str> {
+        deck.cards.iter().map(|card| card.front.text.as_str()).collect()
+    }
+
+    #[test]
+    fn removing_middle_card_keeps_order() {
+        let mut deck = deck(&["one", "two", "three"]);
+        let removed = deck.remove_card(1);
+        assert_eq!(removed.front.text, "two");
+        assert_eq!(fronts(&deck), vec!["one", "three"]);
+    }
+
+    #[test]
+    fn compacting_drops_old_reviews_but_keeps_counts() {
+        let today = Date::current();
+        let mut deck = deck(&["one", "two"]);
+        deck.cards[0].history = vec![
+            (today.sub_days(100), Result::Wrong),
+            (today.sub_days(90), Result::Easy),
+            (today.sub_days(2), Result::Difficult),
+            (today.clone(), Result::Easy),
+        ];
+        // Only reviewed long ago, the last review stays
+        deck.cards[1].history = vec![
+            (today.sub_days(200), Result::Wrong),
+            (today.sub_days(150), Result::Easy),
+        ];
+        let stats_before = deck.stats(30);
+
+        assert_eq!(deck.compact(30), 3);
+        assert_eq!(deck.cards[0].history.len(), 2);
+        assert_eq!(deck.cards[1].history.len(), 1);
+
+        let stats = deck.stats(30);
+        assert_eq!(stats.total_reviews, stats_before.total_reviews);
+        assert_eq!(stats.wrong, stats_before.wrong);
+        assert_eq!(stats.difficult, stats_before.difficult);
+        assert_eq!(stats.easy, stats_before.easy);
+        assert_eq!(deck.reviewed_today(), (0, 1));
+    }
+
+    #[test]
+    fn health_report_lists_every_problem() {
+        let folder = std::env::temp_dir().join("smart-learner-test-health-report");
+        let _ = std::fs::remove_dir_all(&folder);
+        std::fs::create_dir_all(folder.join("audio")).unwrap();
+        std::fs::write(folder.join("audio").join("found.mp3"), "").unwrap();
+
+        let mut deck = deck(&["leech", "audio", "copy", "Copy ", "fine"]);
+        deck.cards[0].lapses = 8;
+        assert!(deck.cards[0].check_leech(8));
+        deck.cards[1].front.audio_path = Some("missing.mp3".to_string());
+        deck.cards[1].back.audio_path = Some("found.mp3".to_string());
+
+        let report = deck.health_report(&folder);
+        assert_eq!(report.leeches, vec![0]);
+        assert_eq!(report.broken_media.len(), 1);
+        assert_eq!(report.broken_media[0].card, 1);
+        assert!(report.broken_media[0].side == CardSide::Front);
+        assert_eq!(report.broken_media[0].file_name, "missing.mp3");
+        assert_eq!(report.duplicates, vec![(2, 3)]);
+        assert!(report.empty_cards.is_empty());
+        assert!(!report.is_healthy());
+    }
+
+    #[test]
+    fn iterators_yield_matching_cards() {
+        let now = date::current_timestamp();
+        let mut deck = deck(&["new", "due", "later", "suspended", "learning"]);
+        for card in &mut deck.cards[1..] {
+            card.history.push((Date::current(), Result::Easy));
+            card.schedule.repeat_in = 1;
+        }
+        deck.cards[2].schedule.current_repeat_in = 3;
+        deck.cards[3].suspended = true;
+        deck.cards[4].schedule.learning_step = Some(0);
+        deck.cards[4].schedule.learning_due = Some(now + 600);
+
+        let indices = |cards: Vec<(usize, &Card)>| -> Vec<usize> {
+            cards.into_iter().map(|(card_index, _)| card_index).collect()
+        };
+        assert_eq!(indices(deck.iter_due(now).collect()), vec![0, 1]);
+        assert_eq!(indices(deck.iter_due(now + 600).collect()), vec![0, 1, 4]);
+        assert_eq!(indices(deck.iter_new().collect()), vec![0]);
+        assert_eq!(indices(deck.iter_suspended().collect()), vec![3]);
+        assert_eq!(indices(deck.iter_active().collect()), vec![0, 1, 2, 4]);
+    }
+
+    #[test]
+    fn upcoming_matches_served_cards() {
+        let mut deck = deck(&["a", "b", "c", "d", "e", "f", "g"]);
+        for card in &mut deck.cards[..2] {
+            card.history.push((Date::current().sub_days(5), Result::Easy));
+            card.schedule.repeat_in = 1;
+        }
+        for card in &mut deck.cards[3..] {
+            card.tags.push("verbs".to_string());
+        }
+        let limits = DailyLimits {
+            new_cards: 3,
+            ..DailyLimits::default()
+        };
+
+        let upcoming = deck.upcoming(10, None, &limits, ReviewOrder::Random, 7);
+        assert_eq!(upcoming.len(), 5);
+        let tagged = deck.upcoming(10, Some("verbs"), &limits, ReviewOrder::Random, 7);
+        assert!(tagged.iter().all(|&card_index| card_index >= 3));
+        assert_eq!(tagged.len(), 3);
+
+        let mut served = Vec::new();
+        while let Some(card_index) = deck.due_card(&limits, ReviewOrder::Random, 7) {
+            let card = &mut deck.cards[card_index];
+            card.history.push((Date::current(), Result::Easy));
+            card.schedule.repeat_in = 1;
+            card.schedule.current_repeat_in = 1;
+            served.push(card_index);
+        }
+        assert_eq!(served, upcoming);
+    }
+
+    #[test]
+    fn importing_appends_cards_with_text() {
+        let mut deck = deck(&["one"]);
+        let added = deck.import_cards(vec![card("two"), card(" "), card("three")]);
+        assert_eq!(added, 2);
+        assert_eq!(fronts(&deck), vec!["one", "two", "three"]);
+        assert_eq!(deck.import_cards(Vec::new()), 0);
+    }
+
+    #[test]
+    fn search_ignores_case_and_lists_prefix_matches_first() {
+        let mut deck = deck(&["The Big Cat", "cat food", "Dog", "CATALOG"]);
+        deck.cards[2].tags = vec!["Cats".to_string()];
+
+        let results: Vec<usize> = deck
+            .search(false, "  cAt ".to_string())
+            .into_iter()
+            .map(|(card_index, _)| card_index)
+            .collect();
+        assert_eq!(results, vec![1, 3, 0, 2]);
+        assert!(deck.search(false, "bird".to_string()).is_empty());
+    }
+
+    #[test]
+    fn new_cards_of_buried_notes_are_skipped() {
+        let mut deck = deck(&["one", "two", "three"]);
+        deck.cards[0].note_id = Some(1);
+        deck.cards[1].note_id = Some(2);
+        let limits = DailyLimits {
+            buried_notes: HashSet::from([1]),
+            ..DailyLimits::default()
+        };
+        assert_eq!(deck.due_card(&limits, ReviewOrder::Added, 0), Some(1));
+
+        // Siblings that were reviewed before are due like other cards
+        deck.cards[0].history.push((Date::current().sub_days(3), Result::Easy));
+        assert_eq!(deck.due_card(&limits, ReviewOrder::Added, 0), Some(0));
+    }
+
+    #[test]
+    fn imported_cards_get_unique_ids() {
+        let mut deck = deck(&[]);
+        deck.add_card(card("one"));
+        let mut colliding = card("two");
+        colliding.id = 1;
+        deck.import_cards(vec![colliding.clone(), colliding]);
+
+        let ids: Vec<u64> = deck.cards.iter().map(|card| card.id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+        assert_eq!(deck.card_by_id(2).map(|(card_index, _)| card_index), Some(1));
+        assert!(deck.card_by_id(4).is_none());
+    }
+
+    #[test]
+    fn missing_and_repeated_ids_are_assigned() {
+        let mut deck = deck(&["one", "two", "three", "four"]);
+        deck.cards[1].id = 7;
+        deck.cards[2].id = 7;
+        deck.assign_card_ids();
+
+        let ids: Vec<u64> = deck.cards.iter().map(|card| card.id).collect();
+        assert_eq!(ids, vec![8, 7, 9, 10]);
+        deck.remove_card(3);
+        assert_eq!(deck.add_card(card("five")), 3);
+        assert_eq!(deck.cards[3].id, 11);
+    }
+}
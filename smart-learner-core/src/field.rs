@@ -1,6 +1,22 @@
 use serde_derive::{Deserialize, Serialize};
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Field {
     pub text: String,
     pub audio_path: Option<String>,
+    /// File name in the images folder.
+    #[serde(default)]
+    pub image_path: Option<String>,
+    /// The image stays hidden while the field is asked and is shown with the answer.
+    #[serde(default)]
+    pub image_after_answer: bool,
+}
+
+impl Field {
+    /// Image shown for the field, None while it's asked and its image waits for the answer.
+    pub fn visible_image(&self, answer_shown: bool) -> Option<&String> {
+        if self.image_after_answer && !answer_shown {
+            return None;
+        }
+        self.image_path.as_ref()
+    }
 }
\ No newline at end of file
@@ -1,3 +1,4 @@
+pub mod answer;
 pub mod card;
 pub mod field;
 pub mod result;
@@ -1,5 +1,38 @@
+use serde_derive::{Deserialize, Serialize};
+
+/// Names are spelled out so renaming variants doesn't change saved data.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
 pub enum Result {
+    #[serde(rename = "wrong")]
     Wrong,
+    #[serde(rename = "difficult")]
     Difficult,
+    #[serde(rename = "easy")]
     Easy,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAMES: [(Result, &str); 3] = [
+        (Result::Wrong, "\"wrong\""),
+        (Result::Difficult, "\"difficult\""),
+        (Result::Easy, "\"easy\""),
+    ];
+
+    #[test]
+    fn results_serialize_to_stable_names() {
+        for (result, name) in NAMES {
+            assert_eq!(serde_json::to_string(&result).unwrap(), name);
+        }
+    }
+
+    #[test]
+    fn saved_names_deserialize_to_results() {
+        for (result, name) in NAMES {
+            assert!(serde_json::from_str::<Result>(name).unwrap() == result);
+        }
+        assert!(serde_json::from_str::<Result>("\"Easy\"").is_err());
+    }
+}
@@ -4,9 +4,36 @@ use eframe::{
     egui::{self, Id, Key},
     epaint::Vec2,
 };
+use egui_extras::RetainedImage;
 use egui_file::FileDialog;
-use smart_learner_core::result::Result;
-use smart_learner_helper::app::App;
+use smart_learner_core::{
+    answer::{AnswerComparison, CharDiff, Strictness},
+    card::{CardKind, CardSide},
+    date::Date,
+    deck::{DeckStatus, HealthReport, ReviewOrder, TextDirection},
+    result::Result,
+};
+use smart_learner_helper::{
+    app::{App, BrowserSort},
+    config::Theme,
+    export::ExportFormat,
+};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// How many days the answers on the statistics page cover.
+const STATS_DAYS: u64 = 30;
+/// Weeks the activity calendar covers.
+const HEATMAP_WEEKS: u64 = 20;
+const HEATMAP_CELL_SIZE: f32 = 12.0;
+/// Days of reviews kept when a deck is compacted, older reviews aren't shown anywhere.
+const COMPACT_KEEP_DAYS: u32 = 365;
+/// Largest size card images are shown at.
+const IMAGE_MAX_SIZE: Vec2 = Vec2::new(400.0, 300.0);
 
 fn main() {
     env_logger::init();
@@ -26,7 +53,62 @@ struct GuiApp {
     app: App,
     state: GuiState,
     new_deck_name: String,
+    /// Deck chosen with the arrow keys on the main page, Enter opens it.
+    selected_deck_index: usize,
     file_dialog: Option<FileDialog>,
+    export_dialog: Option<FileDialog>,
+    import_dialog: Option<FileDialog>,
+    csv_dialog: Option<FileDialog>,
+    deck_export_dialog: Option<FileDialog>,
+    anki_dialog: Option<FileDialog>,
+    front_image_dialog: Option<FileDialog>,
+    bundle_export_dialog: Option<FileDialog>,
+    bundle_import_dialog: Option<FileDialog>,
+    back_image_dialog: Option<FileDialog>,
+    backup_dialog: Option<FileDialog>,
+    /// Loaded card images, None if the file couldn't be read.
+    images: HashMap<PathBuf, Option<RetainedImage>>,
+    export_format: ExportFormat,
+    /// Name typed in the deck settings.
+    deck_name: String,
+    /// Description being edited in the deck settings.
+    deck_description: String,
+    /// Tag added to all search results in the browser.
+    bulk_tag: String,
+    /// The browser lists cards with the same front in the current deck.
+    show_duplicates: bool,
+    /// Problems of the deck open in the deck settings, once they were checked.
+    health_report: Option<HealthReport>,
+    /// Learning steps typed in the settings, minutes separated by spaces.
+    learning_steps: String,
+    /// Non-fatal message shown above the menu.
+    message: Option<String>,
+    /// Deletion waiting for the user to confirm it.
+    pending_delete: Option<PendingDelete>,
+    show_revision_overlay: bool,
+    /// When the deck was switched with Tab during revision.
+    deck_switched_at: Option<Instant>,
+    answer_shown_at: Instant,
+}
+
+#[derive(Clone, Copy)]
+enum PendingDelete {
+    /// The card open in the editor.
+    Card,
+    Deck(usize),
+    /// Merging deletes the source deck.
+    Merge {
+        source: usize,
+        dest: usize,
+    },
+}
+
+/// Inline styles of a piece of card text written in Markdown.
+#[derive(Clone, Copy, Default)]
+struct MarkdownStyle {
+    bold: bool,
+    italics: bool,
+    code: bool,
 }
 
 enum GuiState {
@@ -37,25 +119,497 @@ enum GuiState {
     RevisingWithoutAnswer,
     RevisingWithAnswer,
     Settings,
+    DeckSettings,
+    Stats,
 }
 
 impl Default for GuiApp {
     fn default() -> Self {
+        let app = App::new();
+        let message = app
+            .config_reset
+            .then(|| {
+                "Settings couldn't be loaded and were reset, the old file is kept as .toml.bak"
+                    .to_string()
+            })
+            .or_else(|| app.check_folder().err())
+            .or_else(|| app.deck_errors());
+        let learning_steps = app
+            .config
+            .learning_steps
+            .iter()
+            .map(|minutes| minutes.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
         Self {
-            app: App::new(),
+            app,
             state: GuiState::Main,
             new_deck_name: "".to_string(),
+            selected_deck_index: 0,
             file_dialog: None,
+            export_dialog: None,
+            import_dialog: None,
+            csv_dialog: None,
+            deck_export_dialog: None,
+            anki_dialog: None,
+            front_image_dialog: None,
+            bundle_export_dialog: None,
+            bundle_import_dialog: None,
+            back_image_dialog: None,
+            backup_dialog: None,
+            images: HashMap::new(),
+            export_format: ExportFormat::Csv,
+            deck_name: String::new(),
+            deck_description: String::new(),
+            bulk_tag: String::new(),
+            show_duplicates: false,
+            health_report: None,
+            learning_steps,
+            message,
+            pending_delete: None,
+            show_revision_overlay: false,
+            deck_switched_at: None,
+            answer_shown_at: Instant::now(),
+        }
+    }
+}
+
+impl GuiApp {
+    /// Layout for card text, follows the text direction of the current deck.
+    fn card_text_layout(&self) -> egui::Layout {
+        match self.app.text_direction() {
+            TextDirection::Ltr => egui::Layout::top_down(egui::Align::Min),
+            TextDirection::Rtl => egui::Layout::top_down(egui::Align::Max),
+        }
+    }
+
+    /// Question in heading font.
+    fn question_layout(&self, ui: &egui::Ui) -> egui::text::LayoutJob {
+        let mut job = egui::text::LayoutJob::default();
+        self.append_card_text(ui, &mut job, &self.app.get_question(), false);
+        job
+    }
+
+    /// Answer in heading font, with cloze spans highlighted.
+    fn answer_layout(&self, ui: &egui::Ui) -> egui::text::LayoutJob {
+        let mut job = egui::text::LayoutJob::default();
+        for (text, highlighted) in self.app.get_answer_parts() {
+            self.append_card_text(ui, &mut job, &text, highlighted);
+        }
+        job
+    }
+
+    /// Appends card text in heading font, rendering Markdown if it's turned on.
+    fn append_card_text(
+        &self,
+        ui: &egui::Ui,
+        job: &mut egui::text::LayoutJob,
+        text: &str,
+        highlighted: bool,
+    ) {
+        let pieces = if self.app.config.render_markdown {
+            markdown_pieces(text)
+        } else {
+            vec![(text.to_string(), MarkdownStyle::default())]
+        };
+
+        let font_id = egui::TextStyle::Heading.resolve(ui.style());
+        for (piece, style) in pieces {
+            let mut format = egui::TextFormat {
+                font_id: font_id.clone(),
+                color: ui.visuals().text_color(),
+                italics: style.italics,
+                ..Default::default()
+            };
+            // egui has no bold fonts, bold text is drawn in the strong color instead
+            if style.bold {
+                format.color = ui.visuals().strong_text_color();
+            }
+            if style.code {
+                format.font_id = egui::FontId::monospace(font_id.size);
+                format.background = ui.visuals().code_bg_color;
+            }
+            if highlighted {
+                format.background = ui.visuals().selection.bg_fill;
+            }
+            job.append(&piece, 0.0, format);
+        }
+    }
+
+    /// Shows the image at the path, loading it on first use.
+    fn show_image(&mut self, ui: &mut egui::Ui, path: &Path) {
+        let image = self.images.entry(path.to_path_buf()).or_insert_with(|| {
+            let image = fs::read(path)
+                .map_err(|error| error.to_string())
+                .and_then(|bytes| {
+                    RetainedImage::from_image_bytes(path.display().to_string(), &bytes)
+                });
+            match image {
+                Ok(image) => Some(image),
+                Err(error) => {
+                    log::error!("Can't load image {}: {}", path.display(), error);
+                    None
+                }
+            }
+        });
+
+        match image {
+            Some(image) => {
+                image.show_max_size(ui, IMAGE_MAX_SIZE);
+            }
+            None => {
+                ui.label("Image can't be shown.");
+            }
+        }
+    }
+
+    /// Lists the problems of the current deck with buttons that fix them.
+    /// Returns true if something was fixed, the report is outdated then.
+    fn health_view(&mut self, ui: &mut egui::Ui, report: &HealthReport) -> bool {
+        let deck = self.app.current_deck_index();
+        let cards = &self.app.decks[deck].value.cards;
+        let (mut remove_media, mut delete_copies, mut delete_empty) = (false, false, false);
+        ui.group(|ui| {
+            if report.is_healthy() {
+                ui.label("No problems found.");
+                return;
+            }
+
+            if !report.leeches.is_empty() {
+                ui.label(format!("Leeches: {}", report.leeches.len()));
+                for &card in &report.leeches {
+                    ui.weak(&cards[card].front.text);
+                }
+            }
+
+            if !report.broken_media.is_empty() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("Missing media: {}", report.broken_media.len()));
+                    remove_media = ui.small_button("Remove links").clicked();
+                });
+                for broken in &report.broken_media {
+                    ui.weak(format!(
+                        "{}: {}",
+                        cards[broken.card].front.text, broken.file_name
+                    ));
+                }
+            }
+
+            if !report.duplicates.is_empty() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("Duplicates: {}", report.duplicates.len()));
+                    delete_copies = ui.small_button("Delete copies").clicked();
+                });
+                for &(kept, _) in &report.duplicates {
+                    ui.weak(&cards[kept].front.text);
+                }
+            }
+
+            if !report.empty_cards.is_empty() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("Empty cards: {}", report.empty_cards.len()));
+                    delete_empty = ui.small_button("Delete them").clicked();
+                });
+            }
+        });
+
+        if remove_media {
+            self.app.remove_broken_media(deck);
+        }
+        if delete_copies {
+            self.app.delete_duplicate_copies(deck);
+        }
+        if delete_empty {
+            self.app.delete_empty_cards(deck);
+        }
+        remove_media || delete_copies || delete_empty
+    }
+
+    /// Tab jumps to the next deck with due cards during revision.
+    /// Returns true if the deck was switched in this frame.
+    fn deck_switching(&mut self, ctx: &egui::Context) -> bool {
+        let switched = ctx.input(|i| i.key_pressed(Key::Tab)) && self.app.switch_to_next_due_deck();
+        if switched {
+            self.state = GuiState::RevisingWithoutAnswer;
+            self.deck_switched_at = Some(Instant::now());
+            ctx.request_repaint();
+        }
+
+        if let Some(switched_at) = self.deck_switched_at {
+            if switched_at.elapsed() < Duration::from_secs(2) {
+                egui::TopBottomPanel::top(Id::new("deck_switch")).show(ctx, |ui| {
+                    ui.label(format!("Switched to {}", self.app.current_deck_name()));
+                });
+                ctx.request_repaint_after(Duration::from_millis(100));
+            } else {
+                self.deck_switched_at = None;
+            }
+        }
+
+        switched
+    }
+
+    /// Adds a card to the current deck and opens it in the editor.
+    /// Chooses the current deck.
+    fn deck_combo_box(&mut self, ui: &mut egui::Ui) {
+        let mut selected_deck = self.app.current_deck_index();
+        egui::ComboBox::from_label("Deck")
+            .selected_text(self.app.current_deck_name())
+            .show_ui(ui, |ui| {
+                for (index, deck) in self.app.decks.iter().enumerate() {
+                    ui.selectable_value(&mut selected_deck, index, &deck.value.name);
+                }
+            });
+        if selected_deck != self.app.current_deck_index() {
+            self.app.set_current_deck(selected_deck);
+        }
+    }
+
+    fn new_card_button(&mut self, ui: &mut egui::Ui) {
+        if ui.button("New card in this deck").clicked() {
+            if self.app.create_card() {
+                self.state = GuiState::Editor;
+            } else {
+                self.message = Some("There are no decks, create one first".to_string());
+            }
+        }
+    }
+
+    fn session_progress_bar(&self, ui: &mut egui::Ui) {
+        let (done, total) = self.app.session_progress();
+        if total != 0 {
+            ui.add(
+                egui::ProgressBar::new(done as f32 / total as f32)
+                    .text(format!("{} / {}", done, total)),
+            );
+        }
+
+        let seconds = self.app.session_elapsed().as_secs();
+        ui.label(format!("Studying for {}:{:02}", seconds / 60, seconds % 60));
+        ui.ctx().request_repaint_after(Duration::from_secs(1));
+    }
+
+    /// Undo button for the last review, also bound to Ctrl+Z.
+    /// Returns true if a review was undone.
+    fn review_undo(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) -> bool {
+        if !self.app.can_undo_review() {
+            return false;
+        }
+
+        let undo = ui.button("Undo").clicked()
+            || ctx.input(|i| i.modifiers.command && i.key_pressed(Key::Z));
+        if undo {
+            self.app.stop_audio();
+            self.app.undo_last_review();
+            self.state = GuiState::RevisingWithoutAnswer;
+        }
+        undo
+    }
+
+    /// Calendar of reviews in all decks, a column per week, days with more reviews are stronger.
+    fn activity_heatmap(&self, ui: &mut egui::Ui) {
+        let activity = self.app.activity_by_day(HEATMAP_WEEKS * 7);
+        let max_count = activity.iter().map(|(_, count)| *count).max().unwrap_or(0);
+
+        let (rect, response) = ui.allocate_exact_size(
+            Vec2::new(HEATMAP_WEEKS as f32, 7.0) * HEATMAP_CELL_SIZE,
+            egui::Sense::hover(),
+        );
+        let cell_rect = |index: usize| {
+            let position =
+                rect.min + Vec2::new((index / 7) as f32, (index % 7) as f32) * HEATMAP_CELL_SIZE;
+            egui::Rect::from_min_size(position, Vec2::splat(HEATMAP_CELL_SIZE)).shrink(1.0)
+        };
+
+        let painter = ui.painter();
+        for (index, (_, count)) in activity.iter().enumerate() {
+            let color = if *count == 0 {
+                ui.visuals().faint_bg_color
+            } else {
+                let strength = 0.3 + 0.7 * *count as f32 / max_count as f32;
+                ui.visuals().selection.bg_fill.gamma_multiply(strength)
+            };
+            painter.rect_filled(cell_rect(index), 2.0, color);
+        }
+
+        if let Some(pointer) = response.hover_pos() {
+            let hovered = (0..activity.len()).find(|&index| cell_rect(index).contains(pointer));
+            if let Some((date, count)) = hovered.map(|index| &activity[index]) {
+                response.on_hover_text_at_pointer(format!("{}: {} reviews", date, count));
+            }
+        }
+    }
+
+    /// Asks before deleting, Escape cancels.
+    fn delete_confirmation(&mut self, ctx: &egui::Context) {
+        let question = match self.pending_delete {
+            Some(PendingDelete::Card) => "Delete this card?".to_string(),
+            Some(PendingDelete::Deck(index)) => match self.app.decks.get(index) {
+                Some(deck) => format!("Delete the deck {}?", deck.value.name),
+                // The decks were reloaded in the meantime
+                None => {
+                    self.pending_delete = None;
+                    return;
+                }
+            },
+            Some(PendingDelete::Merge { source, dest }) => {
+                match (self.app.decks.get(source), self.app.decks.get(dest)) {
+                    (Some(source), Some(dest)) => format!(
+                        "Move all cards of {} into {} and delete {}?",
+                        source.value.name, dest.value.name, source.value.name
+                    ),
+                    _ => {
+                        self.pending_delete = None;
+                        return;
+                    }
+                }
+            }
+            None => return,
+        };
+
+        let mut confirmed = false;
+        let mut cancelled = ctx.input(|i| i.key_pressed(Key::Escape));
+        egui::Window::new("Confirm")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(question);
+                ui.horizontal(|ui| {
+                    confirmed = ui.button("Yes").clicked();
+                    cancelled |= ui.button("Cancel").clicked();
+                });
+            });
+
+        if cancelled {
+            self.pending_delete = None;
+        } else if confirmed {
+            match self.pending_delete.take() {
+                Some(PendingDelete::Card) => {
+                    self.app.delete_card();
+                    self.state = GuiState::Main;
+                }
+                // Decks are only deleted from the main page, so no revision is running
+                Some(PendingDelete::Deck(index)) => {
+                    if let Err(error) = self.app.delete_deck(index) {
+                        self.message = Some(format!("Can't delete the deck file: {}", error));
+                    }
+                }
+                Some(PendingDelete::Merge { source, dest }) => {
+                    if let Err(error) = self.app.merge_decks(source, dest) {
+                        self.message = Some(format!("Can't delete the merged deck: {}", error));
+                    }
+                    self.state = GuiState::Main;
+                }
+                None => {}
+            }
+        }
+    }
+
+    /// Quick stats window, toggled with S during revision.
+    fn revision_overlay(&mut self, ctx: &egui::Context) {
+        if ctx.input(|i| i.key_pressed(Key::S)) {
+            self.show_revision_overlay = !self.show_revision_overlay;
+        }
+
+        if let Some(info) = self.app.revision_overlay_info() {
+            egui::Window::new("Quick stats")
+                .open(&mut self.show_revision_overlay)
+                .show(ctx, |ui| {
+                    ui.label(format!("Interval: {} days", info.interval));
+                    ui.label(format!(
+                        "Due cards: {} of {}",
+                        info.due_cards, info.total_cards
+                    ));
+                });
         }
     }
 }
 
 impl eframe::App for GuiApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    fn on_close_event(&mut self) -> bool {
+        if let Err(error) = self.app.save() {
+            log::error!("Can't save decks: {}", error);
+        }
+        true
+    }
+
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        let dark_mode = match self.app.config.theme {
+            Theme::System => frame.info().system_theme != Some(eframe::Theme::Light),
+            Theme::Light => false,
+            Theme::Dark => true,
+        };
+        if ctx.style().visuals.dark_mode != dark_mode {
+            ctx.set_visuals(if dark_mode {
+                egui::Visuals::dark()
+            } else {
+                egui::Visuals::light()
+            });
+        }
+
+        self.app.autosave();
+        if self.app.config.autosave_interval_secs != 0 {
+            ctx.request_repaint_after(Duration::from_secs(self.app.config.autosave_interval_secs));
+        }
+
+        self.app.process_folder_events();
+        if self.app.config.watch_folder {
+            ctx.request_repaint_after(Duration::from_secs(1));
+        }
+
+        let revising = matches!(
+            self.state,
+            GuiState::RevisingWithoutAnswer | GuiState::RevisingWithAnswer
+        );
+        if revising && ctx.input(|i| !i.events.is_empty()) {
+            self.app.record_activity();
+        }
+
+        self.delete_confirmation(ctx);
+
         // Showing the page
         match self.state {
             GuiState::Main => {
+                // Arrow keys choose a deck unless a text field is being typed in
+                let mut opened_deck = None;
+                let mut selection_moved = false;
+                if !self.app.decks.is_empty()
+                    && self.pending_delete.is_none()
+                    && !ctx.wants_keyboard_input()
+                {
+                    let last = self.app.decks.len() - 1;
+                    self.selected_deck_index = self.selected_deck_index.min(last);
+                    ctx.input(|i| {
+                        if i.key_pressed(Key::ArrowDown) && self.selected_deck_index < last {
+                            self.selected_deck_index += 1;
+                            selection_moved = true;
+                        }
+                        if i.key_pressed(Key::ArrowUp) && self.selected_deck_index > 0 {
+                            self.selected_deck_index -= 1;
+                            selection_moved = true;
+                        }
+                        if i.key_pressed(Key::Enter) {
+                            opened_deck = Some(self.selected_deck_index);
+                        }
+                    });
+                }
+
                 egui::CentralPanel::default().show(ctx, |ui| {
+                    // Recently studied decks
+                    let recent_decks = self.app.recent_decks();
+                    if !recent_decks.is_empty() {
+                        ui.group(|ui| {
+                            ui.label("Continue:");
+                            for index in recent_decks {
+                                if ui.link(&self.app.decks[index].value.name).clicked() {
+                                    self.app.open_deck(index);
+                                    self.state = GuiState::RevisingWithoutAnswer;
+                                }
+                            }
+                        });
+                    }
+
                     // Thingy to create new decks
                     ui.horizontal(|ui| {
                         let label = ui.label("Deck name:");
@@ -66,16 +620,88 @@ impl eframe::App for GuiApp {
                             self.app.new_deck(self.new_deck_name.clone());
                             self.new_deck_name = String::new();
                         }
+
+                        if ui.button("Import Anki deck").clicked() {
+                            let mut dialog =
+                                FileDialog::open_file(None).default_size(Vec2::new(480.0, 300.0));
+                            dialog.open();
+                            self.anki_dialog = Some(dialog);
+                        }
+                    });
+
+                    if let Some(dialog) = &mut self.anki_dialog {
+                        if dialog.show(ctx).selected() {
+                            if let Some(file) = dialog.path() {
+                                self.message = Some(match self.app.import_anki(file.as_path()) {
+                                    Ok(count) => format!("Imported {} cards.", count),
+                                    Err(error) => format!("Couldn't import: {}", error),
+                                });
+                            }
+                        }
+                    }
+
+                    ui.horizontal(|ui| {
+                        let label = ui.label("Only revise cards tagged:");
+                        ui.text_edit_singleline(&mut self.app.revision_tag)
+                            .labelled_by(label.id);
+                        let tag = self.app.revision_tag.trim().to_string();
+                        if ui
+                            .add_enabled(!tag.is_empty(), egui::Button::new("Revise in all decks"))
+                            .clicked()
+                        {
+                            self.app.start_tag_session(tag);
+                            self.state = GuiState::RevisingWithoutAnswer;
+                        }
                     });
 
                     // Displaying decks
                     egui::containers::ScrollArea::vertical().show(ui, |ui| {
+                        let mut deleted_deck = None;
+                        let mut configured_deck = None;
                         for (index, deck) in self.app.decks.iter().enumerate() {
-                            if ui.link(&deck.value.name).clicked() {
-                                self.state = GuiState::RevisingWithoutAnswer;
-                                self.app.current_deck = index;
+                            ui.horizontal(|ui| {
+                                let mut name = egui::RichText::new(&deck.value.name);
+                                if index == self.selected_deck_index {
+                                    name = name.background_color(ui.visuals().selection.bg_fill);
+                                }
+                                let link = ui.link(name);
+                                if index == self.selected_deck_index && selection_moved {
+                                    link.scroll_to_me(None);
+                                }
+                                if link.clicked() {
+                                    self.selected_deck_index = index;
+                                    opened_deck = Some(index);
+                                }
+                                if ui
+                                    .small_button("⚙")
+                                    .on_hover_text("Deck settings")
+                                    .clicked()
+                                {
+                                    configured_deck = Some(index);
+                                }
+                                if ui.small_button("✕").on_hover_text("Delete deck").clicked() {
+                                    deleted_deck = Some(index);
+                                }
+                            });
+                            if !deck.value.description.is_empty() {
+                                ui.weak(&deck.value.description);
                             }
                         }
+
+                        if let Some(index) = opened_deck {
+                            self.state = GuiState::RevisingWithoutAnswer;
+                            self.app.open_deck(index);
+                        }
+                        if let Some(index) = configured_deck {
+                            self.app.set_current_deck(index);
+                            self.deck_name = self.app.current_deck_name();
+                            self.deck_description = self.app.decks[index].value.description.clone();
+                            self.health_report = None;
+                            self.state = GuiState::DeckSettings;
+                        }
+                        if let Some(index) = deleted_deck {
+                            self.pending_delete = Some(PendingDelete::Deck(index));
+                        }
                     });
                 });
             }
@@ -84,92 +710,464 @@ impl eframe::App for GuiApp {
                 egui::CentralPanel::default().show(ctx, |ui| {
                     ui.group(|ui| {
                         let label = ui.label("Front:");
-                        ui.text_edit_multiline(&mut self.app.card_front)
-                            .labelled_by(label.id);
+                        ui.with_layout(self.card_text_layout(), |ui| {
+                            ui.text_edit_multiline(&mut self.app.card_front)
+                                .labelled_by(label.id);
+                        });
 
-                        if ui.button("Choose audio").clicked() {
+                        ui.horizontal(|ui| {
+                            if ui.button("Choose audio").clicked() {
+                                let mut dialog = FileDialog::open_file(None)
+                                    .default_size(Vec2::new(480.0, 300.0));
+                                dialog.open();
+                                self.file_dialog = Some(dialog);
+                            }
+                            if ui.button("Generate audio").clicked() {
+                                let lang = self.app.config.tts_language.clone();
+                                if let Err(error) = self.app.generate_tts(CardSide::Front, &lang) {
+                                    self.message = Some(error);
+                                }
+                            }
+                        });
+
+                        if let Some(dialog) = &mut self.file_dialog {
+                            if dialog.show(ctx).selected() {
+                                if let Some(file) = dialog.path() {
+                                    if let Err(error) = self.app.change_front_audio(file.as_path())
+                                    {
+                                        self.message =
+                                            Some(format!("Couldn't add the audio: {}", error));
+                                    }
+                                }
+                            }
+                        }
+
+                        if ui.button("Choose image").clicked() {
                             let mut dialog =
                                 FileDialog::open_file(None).default_size(Vec2::new(480.0, 300.0));
                             dialog.open();
-                            self.file_dialog = Some(dialog);
+                            self.front_image_dialog = Some(dialog);
                         }
 
-                        if let Some(dialog) = &mut self.file_dialog {
+                        if let Some(dialog) = &mut self.front_image_dialog {
                             if dialog.show(ctx).selected() {
                                 if let Some(file) = dialog.path() {
-                                    self.app.change_front_audio(
-                                        file.as_path().to_str().unwrap().to_string(),
-                                    );
+                                    if let Err(error) = self.app.change_front_image(file.as_path())
+                                    {
+                                        self.message =
+                                            Some(format!("Couldn't add the image: {}", error));
+                                    }
                                 }
                             }
                         }
+
+                        let mut after_answer = self.app.front_image_after_answer();
+                        if ui
+                            .checkbox(&mut after_answer, "Show the image only with the answer")
+                            .changed()
+                        {
+                            self.app.set_front_image_after_answer(after_answer);
+                        }
                     });
 
                     ui.group(|ui| {
                         let label = ui.label("Back:");
-                        ui.text_edit_multiline(&mut self.app.card_back)
-                            .labelled_by(label.id);
+                        ui.with_layout(self.card_text_layout(), |ui| {
+                            ui.text_edit_multiline(&mut self.app.card_back)
+                                .labelled_by(label.id);
+                        });
+
+                        ui.horizontal(|ui| {
+                            if ui.button("Choose audio").clicked() {
+                                let mut dialog = FileDialog::open_file(None)
+                                    .default_size(Vec2::new(480.0, 300.0));
+                                dialog.open();
+                                self.file_dialog = Some(dialog);
+                            }
+                            if ui.button("Generate audio").clicked() {
+                                let lang = self.app.config.tts_language.clone();
+                                if let Err(error) = self.app.generate_tts(CardSide::Back, &lang) {
+                                    self.message = Some(error);
+                                }
+                            }
+                        });
 
-                        if ui.button("Choose audio").clicked() {
+                        if let Some(dialog) = &mut self.file_dialog {
+                            if dialog.show(ctx).selected() {
+                                if let Some(file) = dialog.path() {
+                                    if let Err(error) = self.app.change_back_audio(file.as_path()) {
+                                        self.message =
+                                            Some(format!("Couldn't add the audio: {}", error));
+                                    }
+                                }
+                            }
+                        }
+
+                        if ui.button("Choose image").clicked() {
                             let mut dialog =
                                 FileDialog::open_file(None).default_size(Vec2::new(480.0, 300.0));
                             dialog.open();
-                            self.file_dialog = Some(dialog);
+                            self.back_image_dialog = Some(dialog);
                         }
 
-                        if let Some(dialog) = &mut self.file_dialog {
+                        if let Some(dialog) = &mut self.back_image_dialog {
                             if dialog.show(ctx).selected() {
                                 if let Some(file) = dialog.path() {
-                                    self.app.change_back_audio(
-                                        file.as_path().to_str().unwrap().to_string(),
-                                    );
+                                    if let Err(error) = self.app.change_back_image(file.as_path()) {
+                                        self.message =
+                                            Some(format!("Couldn't add the image: {}", error));
+                                    }
                                 }
                             }
                         }
                     });
 
+                    ui.horizontal(|ui| {
+                        let label = ui.label("Speech language:");
+                        ui.text_edit_singleline(&mut self.app.config.tts_language)
+                            .labelled_by(label.id)
+                            .on_hover_text("espeak voice used by Generate audio, like en or de");
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.selectable_value(&mut self.app.card_kind, CardKind::Basic, "Basic");
+                        ui.selectable_value(&mut self.app.card_kind, CardKind::Cloze, "Cloze")
+                            .on_hover_text(
+                                "Text in {{...}} on the front is hidden in the question",
+                            );
+                        ui.selectable_value(
+                            &mut self.app.card_kind,
+                            CardKind::TypeAnswer,
+                            "Type answer",
+                        )
+                        .on_hover_text("The answer is typed and compared with the back");
+                    });
+
+                    if self.app.card_kind == CardKind::Basic {
+                        ui.checkbox(&mut self.app.card_bidirectional, "Also study back to front");
+                    }
+
+                    ui.horizontal(|ui| {
+                        let label = ui.label("Tags (comma separated):");
+                        ui.text_edit_singleline(&mut self.app.card_tags)
+                            .labelled_by(label.id);
+                    });
+
+                    if let Some(id) = self.app.current_card_id() {
+                        ui.label(format!("Card id: {}", id));
+                    }
+
+                    ui.horizontal(|ui| {
+                        ui.label("Due in");
+                        ui.add(egui::DragValue::new(&mut self.app.card_due_in));
+                        ui.label("days");
+                        if ui.button("Reschedule").clicked() {
+                            self.app.reschedule_card(self.app.card_due_in);
+                        }
+                    });
+
+                    if let Some(card_index) = self.app.current_card_index() {
+                        let from_deck = self.app.current_deck_index();
+                        let mut to_deck = from_deck;
+                        egui::ComboBox::from_label("Move to deck")
+                            .selected_text(self.app.current_deck_name())
+                            .show_ui(ui, |ui| {
+                                for (index, deck) in self.app.decks.iter().enumerate() {
+                                    ui.selectable_value(&mut to_deck, index, &deck.value.name);
+                                }
+                            });
+                        if to_deck != from_deck {
+                            self.app.move_card(from_deck, card_index, to_deck);
+                        }
+                    }
+
+                    let suspend_text = if self.app.is_card_suspended() {
+                        "Unsuspend"
+                    } else {
+                        "Suspend"
+                    };
+                    if ui
+                        .button(suspend_text)
+                        .on_hover_text("Suspended cards aren't revised")
+                        .clicked()
+                    {
+                        self.app.toggle_suspended();
+                    }
+
+                    ui.horizontal(|ui| {
+                        if ui
+                            .button("Export card")
+                            .on_hover_text("Saves the card with its audio and images as a zip")
+                            .clicked()
+                        {
+                            let mut dialog =
+                                FileDialog::save_file(None).default_size(Vec2::new(480.0, 300.0));
+                            dialog.open();
+                            self.bundle_export_dialog = Some(dialog);
+                        }
+                        if ui
+                            .button("Import card")
+                            .on_hover_text("Adds a card exported from another deck to this one")
+                            .clicked()
+                        {
+                            let mut dialog =
+                                FileDialog::open_file(None).default_size(Vec2::new(480.0, 300.0));
+                            dialog.open();
+                            self.bundle_import_dialog = Some(dialog);
+                        }
+                    });
+
+                    if let Some(dialog) = &mut self.bundle_export_dialog {
+                        if dialog.show(ctx).selected() {
+                            if let (Some(file), Some(card)) =
+                                (dialog.path(), self.app.current_card_index())
+                            {
+                                if let Err(error) = self.app.export_card_bundle(
+                                    self.app.current_deck_index(),
+                                    card,
+                                    file.as_path(),
+                                ) {
+                                    self.message =
+                                        Some(format!("Couldn't export the card: {}", error));
+                                }
+                            }
+                        }
+                    }
+
+                    if let Some(dialog) = &mut self.bundle_import_dialog {
+                        if dialog.show(ctx).selected() {
+                            if let Some(file) = dialog.path() {
+                                if let Err(error) = self.app.import_card_bundle(file.as_path()) {
+                                    self.message =
+                                        Some(format!("Couldn't import the card: {}", error));
+                                }
+                            }
+                        }
+                    }
+
                     if ui.button("Save").clicked() {
-                        self.app.edit_card();
-                        self.state = GuiState::Main;
+                        if self.app.edit_card() {
+                            self.state = GuiState::Main;
+                        } else {
+                            self.message =
+                                Some("The card needs text on the front or the back".to_string());
+                        }
                     }
 
                     if ui.button("Delete").clicked() {
-                        self.app.delete_card();
-                        self.state = GuiState::Main;
+                        self.pending_delete = Some(PendingDelete::Card);
                     }
                 });
             }
 
-            GuiState::Browser => {
-                egui::CentralPanel::default().show(ctx, |ui| {
-                    ui.horizontal(|ui| {
-                        //choose the deck
-                        egui::ComboBox::from_label("Deck")
-                            .selected_text(self.app.current_deck_name())
-                            .show_ui(ui, |ui| {
-                                for (index, deck) in self.app.decks.iter().enumerate() {
-                                    ui.selectable_value(
-                                        &mut self.app.current_deck,
-                                        index,
-                                        &deck.value.name,
-                                    );
+            GuiState::Browser => {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        //choose the deck
+                        self.deck_combo_box(ui);
+                        self.new_card_button(ui);
+
+                        //search field
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.app.search_text)
+                                .hint_text("Text, tag or id:N"),
+                        );
+
+                        //front or back
+                        ui.checkbox(&mut self.app.back_search, "Back search");
+                        ui.checkbox(&mut self.app.fuzzy_search, "Fuzzy");
+                        ui.checkbox(&mut self.app.search_all_decks, "Search all decks");
+
+                        let sort_name = |sort| match sort {
+                            BrowserSort::None => "None",
+                            BrowserSort::DueDate => "Due date",
+                            BrowserSort::Alphabetical => "Alphabetical front",
+                            BrowserSort::RecentlyAdded => "Recently added",
+                        };
+                        egui::ComboBox::from_label("Sort")
+                            .selected_text(sort_name(self.app.browser_sort))
+                            .show_ui(ui, |ui| {
+                                for sort in [
+                                    BrowserSort::None,
+                                    BrowserSort::DueDate,
+                                    BrowserSort::Alphabetical,
+                                    BrowserSort::RecentlyAdded,
+                                ] {
+                                    ui.selectable_value(
+                                        &mut self.app.browser_sort,
+                                        sort,
+                                        sort_name(sort),
+                                    );
+                                }
+                            });
+
+                        if !self.app.decks.is_empty() {
+                            let mut right_to_left = self.app.text_direction() == TextDirection::Rtl;
+                            if ui.checkbox(&mut right_to_left, "Right to left").changed() {
+                                self.app.set_text_direction(if right_to_left {
+                                    TextDirection::Rtl
+                                } else {
+                                    TextDirection::Ltr
+                                });
+                            }
+                        }
+
+                        if !self.app.decks.is_empty() && ui.button("Export to PDF").clicked() {
+                            let mut dialog =
+                                FileDialog::save_file(None).default_size(Vec2::new(480.0, 300.0));
+                            dialog.open();
+                            self.export_dialog = Some(dialog);
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        let label = ui.label("Tag:");
+                        ui.text_edit_singleline(&mut self.bulk_tag)
+                            .labelled_by(label.id);
+                        if ui
+                            .button("Tag all")
+                            .on_hover_text("Adds the tag to every card found")
+                            .clicked()
+                        {
+                            self.app.tag_search_results(self.bulk_tag.clone());
+                        }
+
+                        if ui.button("Find duplicates").clicked() {
+                            self.show_duplicates = true;
+                        }
+                    });
+
+                    if self.show_duplicates && !self.app.decks.is_empty() {
+                        let duplicates = self.app.find_duplicates();
+                        ui.group(|ui| {
+                            ui.horizontal(|ui| {
+                                ui.label(format!(
+                                    "Duplicates in {}: {}",
+                                    self.app.current_deck_name(),
+                                    duplicates.len()
+                                ));
+                                if ui.small_button("Close").clicked() {
+                                    self.show_duplicates = false;
                                 }
                             });
 
-                        //search field
-                        ui.text_edit_singleline(&mut self.app.search_text);
+                            for (kept, duplicate) in duplicates {
+                                let cards =
+                                    &self.app.decks[self.app.current_deck_index()].value.cards;
+                                let front = cards[kept].front.text.clone();
+                                let backs = format!(
+                                    "{} / {}",
+                                    cards[kept].back.text, cards[duplicate].back.text
+                                );
+                                let mut removed = false;
+                                ui.horizontal(|ui| {
+                                    ui.label(front).on_hover_text(backs);
+                                    if ui
+                                        .small_button("Delete copy")
+                                        .on_hover_text("Deletes the card added later")
+                                        .clicked()
+                                    {
+                                        self.app.remove_duplicate(duplicate);
+                                        removed = true;
+                                    }
+                                    if ui
+                                        .small_button("Merge")
+                                        .on_hover_text(
+                                            "Keeps the first card with the tags and back of both",
+                                        )
+                                        .clicked()
+                                    {
+                                        self.app.merge_duplicate(kept, duplicate);
+                                        removed = true;
+                                    }
+                                });
+                                // Indices after the removed card are stale now
+                                if removed {
+                                    break;
+                                }
+                            }
+                        });
+                    }
+
+                    if let Some(dialog) = &mut self.export_dialog {
+                        if dialog.show(ctx).selected() {
+                            if let Some(file) = dialog.path() {
+                                if let Err(error) = self
+                                    .app
+                                    .export_pdf(self.app.current_deck_index(), file.as_path())
+                                {
+                                    self.message =
+                                        Some(format!("Couldn't export the deck: {}", error));
+                                }
+                            }
+                        }
+                    }
 
-                        //front or back
-                        ui.checkbox(&mut self.app.back_search, "Back search");
-                    });
                     //search results
                     egui::containers::ScrollArea::vertical().show(ui, |ui| {
-                        for entry in self.app.search() {
+                        let results = if self.app.search_all_decks {
+                            self.app.search_all()
+                        } else {
+                            let deck = self.app.current_deck_index();
+                            self.app
+                                .search()
+                                .into_iter()
+                                .map(|(card, front, back)| (deck, card, front, back))
+                                .collect()
+                        };
+
+                        for (deck, card, front, back) in results {
                             ui.group(|ui| {
-                                if ui.link(entry.1).clicked() {
-                                    self.app.change_card(entry.0);
+                                if self.app.search_all_decks {
+                                    ui.small(&self.app.decks[deck].value.name);
+                                }
+                                let mut text = egui::RichText::new(front);
+                                let mut back_text = egui::RichText::new(back);
+                                let card_data = &self.app.decks[deck].value.cards[card];
+                                if card_data.is_leech() {
+                                    ui.colored_label(ui.visuals().warn_fg_color, "⚠ Leech")
+                                        .on_hover_text(format!(
+                                            "Answered wrong {} times",
+                                            card_data.lapses
+                                        ));
+                                }
+                                if card_data.suspended {
+                                    text = text.weak();
+                                    back_text = back_text.weak();
+                                    ui.small("Suspended");
+                                }
+                                if card_data.is_new() {
+                                    ui.small("new");
+                                } else {
+                                    let next_due = card_data.next_due();
+                                    if next_due < Date::current() {
+                                        ui.colored_label(
+                                            ui.visuals().warn_fg_color,
+                                            egui::RichText::new(format!(
+                                                "overdue since {}",
+                                                next_due
+                                            ))
+                                            .small(),
+                                        );
+                                    } else {
+                                        ui.small(format!("due {}", next_due));
+                                    }
+                                }
+                                let link = ui.link(text);
+                                if link.clicked() {
+                                    self.app.set_current_deck(deck);
+                                    self.app.change_card(card);
                                     self.state = GuiState::Editor;
                                 }
+                                link.context_menu(|ui| {
+                                    if ui.button("Create similar card").clicked() {
+                                        self.app.set_current_deck(deck);
+                                        self.app.create_similar_card(card);
+                                        self.state = GuiState::Editor;
+                                        ui.close_menu();
+                                    }
+                                });
+                                ui.label(back_text);
                             });
                         }
                     });
@@ -177,56 +1175,189 @@ impl eframe::App for GuiApp {
             }
 
             GuiState::RevisingWithoutAnswer => {
+                self.deck_switching(ctx);
+                self.revision_overlay(ctx);
                 egui::CentralPanel::default().show(ctx, |ui| {
+                    if self.app.should_break() {
+                        ui.heading(format!(
+                            "You've done {} cards, take a break?",
+                            self.app.config.break_after
+                        ));
+                        ui.horizontal(|ui| {
+                            if ui.button("Take a break").clicked() {
+                                self.app.take_break();
+                                self.state = GuiState::Main;
+                            }
+                            if ui.button("Keep going").clicked() {
+                                self.app.take_break();
+                            }
+                        });
+                        return;
+                    }
+
+                    self.session_progress_bar(ui);
+                    self.review_undo(ui, ctx);
+                    self.new_card_button(ui);
+
                     let revision_result = self.app.get_card_for_revision();
 
                     if revision_result.0 {
-                        if revision_result.1 {
-                            self.app.play_front_audio();
+                        if revision_result.1 && self.app.config.autoplay_audio {
+                            if let Err(error) = self.app.play_question_audio() {
+                                self.message = Some(error);
+                            }
+                        }
+
+                        if self.app.in_lapse_review() {
+                            ui.label("Repeating mistakes, answers don't change scheduling.");
+                        } else {
+                            match self.app.due_count() {
+                                1 => ui.heading("1 card remaining"),
+                                count => ui.heading(format!("{} cards remaining", count)),
+                            };
+                        }
+
+                        if let Some((step, step_count)) = self.app.learning_progress() {
+                            ui.label(format!("Learning: step {} of {}", step, step_count));
                         }
 
                         ui.group(|ui| {
-                            ui.heading(&self.app.card_front);
-                            if self.app.front_audio_exists() {
+                            ui.with_layout(self.card_text_layout(), |ui| {
+                                ui.label(self.question_layout(ui));
+                            });
+                            if let Some(path) = self.app.question_image(false) {
+                                self.show_image(ui, &path);
+                            }
+                            if self.app.question_audio_exists() {
                                 if ui.button("Play audio").clicked() {
-                                    self.app.play_front_audio();
+                                    if let Err(error) = self.app.play_question_audio() {
+                                        self.message = Some(error);
+                                    }
                                 }
                             }
                         });
 
+                        let mut answer_typed = false;
+                        if self.app.asks_typed_answer() {
+                            let response = ui.add(
+                                egui::TextEdit::singleline(&mut self.app.typed_answer)
+                                    .hint_text("Type the answer"),
+                            );
+                            answer_typed =
+                                response.lost_focus() && ctx.input(|i| i.key_pressed(Key::Enter));
+                        }
+
                         ui.horizontal(|ui| {
-                            if ui.button("Show answer").clicked()
-                                || ctx.input(|i| i.key_pressed(Key::Space))
-                            {
+                            // Space is typed into the answer field instead
+                            let space_pressed = !ctx.wants_keyboard_input()
+                                && ctx.input(|i| i.key_pressed(Key::Space));
+                            if ui.button("Show answer").clicked() || space_pressed || answer_typed {
+                                self.app.stop_audio();
                                 self.state = GuiState::RevisingWithAnswer;
-                                self.app.play_back_audio();
+                                self.answer_shown_at = Instant::now();
+                                if self.app.config.autoplay_audio {
+                                    if let Err(error) = self.app.play_revealed_audio() {
+                                        self.message = Some(error);
+                                    }
+                                }
                             }
                             if ui.button("Edit").clicked() {
                                 self.state = GuiState::Editor;
                             }
                         });
+
+                        if !self.app.in_lapse_review() {
+                            ui.collapsing("Upcoming cards", |ui| {
+                                for front in self.app.upcoming_cards(10) {
+                                    ui.label(front);
+                                }
+                            });
+                        }
                     } else {
-                        ui.heading("No cards to review.");
+                        match self.app.deck_status(self.app.current_deck_index()) {
+                            DeckStatus::LimitReached => {
+                                ui.heading("No more cards today.");
+                                ui.label("The daily limit for this deck is reached.");
+                            }
+                            DeckStatus::AllDoneToday { next_due } => {
+                                ui.heading("All done!");
+                                if let Some(next_due) = next_due {
+                                    match Date::current().difference(&next_due) {
+                                        0 => {
+                                            // Cards in learning become due within minutes
+                                            ctx.request_repaint_after(Duration::from_secs(1));
+                                            ui.label("More cards later today.")
+                                        }
+                                        1 => ui.label("Next review tomorrow."),
+                                        days => ui.label(format!("Next review in {} days.", days)),
+                                    };
+                                }
+                            }
+                            _ => {
+                                ui.heading("No cards to review.");
+                            }
+                        }
+
+                        if self.app.has_lapses() && ui.button("Repeat mistakes").clicked() {
+                            self.app.start_lapse_review();
+                        }
                     }
                 });
             }
 
             GuiState::RevisingWithAnswer => {
+                // The answered card belongs to the previous deck
+                if self.deck_switching(ctx) {
+                    return;
+                }
+                self.revision_overlay(ctx);
                 egui::CentralPanel::default().show(ctx, |ui| {
+                    self.session_progress_bar(ui);
+                    if self.review_undo(ui, ctx) {
+                        return;
+                    }
+                    self.new_card_button(ui);
+
                     ui.group(|ui| {
-                        ui.heading(&self.app.card_front);
-                        if self.app.front_audio_exists() {
+                        ui.with_layout(self.card_text_layout(), |ui| {
+                            ui.label(self.question_layout(ui));
+                        });
+                        if let Some(path) = self.app.question_image(true) {
+                            self.show_image(ui, &path);
+                        }
+                        if self.app.question_audio_exists() {
                             if ui.button("Play audio").clicked() {
-                                self.app.play_front_audio();
+                                if let Err(error) = self.app.play_question_audio() {
+                                    self.message = Some(error);
+                                }
                             }
                         }
                     });
 
+                    if let Some(comparison) = self.app.compare_typed_answer() {
+                        ui.group(|ui| {
+                            ui.label(if comparison.correct {
+                                "You typed it right!"
+                            } else {
+                                "Your answer:"
+                            });
+                            let diff = typed_answer_diff(ui, &comparison);
+                            ui.label(diff);
+                        });
+                    }
+
                     ui.group(|ui| {
-                        ui.heading(&self.app.card_back);
-                        if self.app.back_audio_exists() {
+                        ui.with_layout(self.card_text_layout(), |ui| {
+                            ui.label(self.answer_layout(ui));
+                        });
+                        if let Some(path) = self.app.answer_image() {
+                            self.show_image(ui, &path);
+                        }
+                        if self.app.answer_audio_exists() {
                             if ui.button("Play audio").clicked() {
-                                self.app.play_back_audio();
+                                if let Err(error) = self.app.play_answer_audio() {
+                                    self.message = Some(error);
+                                }
                             }
                         }
                     });
@@ -234,19 +1365,41 @@ impl eframe::App for GuiApp {
                     ui.horizontal(|ui| {
                         let mut result = None;
 
-                        if ui.button("Wrong").clicked() {
+                        let can_grade = self.app.can_grade(self.answer_shown_at.elapsed());
+                        if !can_grade {
+                            ctx.request_repaint_after(Duration::from_millis(50));
+                        }
+
+                        let key_pressed = |keys: [Key; 2]| {
+                            ctx.input(|i| keys.iter().any(|&key| i.key_pressed(key)))
+                        };
+
+                        if ui
+                            .add_enabled(can_grade, egui::Button::new("Wrong (1)"))
+                            .clicked()
+                            || can_grade && key_pressed([Key::Num1, Key::J])
+                        {
                             result = Some(Result::Wrong);
                         }
 
-                        if ui.button("Difficult").clicked() {
+                        if ui
+                            .add_enabled(can_grade, egui::Button::new("Difficult (2)"))
+                            .clicked()
+                            || can_grade && key_pressed([Key::Num2, Key::K])
+                        {
                             result = Some(Result::Difficult);
                         }
 
-                        if ui.button("Easy").clicked() {
+                        if ui
+                            .add_enabled(can_grade, egui::Button::new("Easy (3)"))
+                            .clicked()
+                            || can_grade && key_pressed([Key::Num3, Key::L])
+                        {
                             result = Some(Result::Easy);
                         }
 
                         if result.is_some() {
+                            self.app.stop_audio();
                             self.app.card_revised(result.unwrap());
                             self.state = GuiState::RevisingWithoutAnswer;
                         }
@@ -258,28 +1411,318 @@ impl eframe::App for GuiApp {
                 egui::CentralPanel::default().show(ctx, |ui| {
                     ui.heading("Add a card");
                     //Displaying decks.
-                    egui::ComboBox::from_label("Deck")
-                        .selected_text(self.app.current_deck_name())
-                        .show_ui(ui, |ui| {
-                            for (index, deck) in self.app.decks.iter().enumerate() {
-                                ui.selectable_value(
-                                    &mut self.app.current_deck,
-                                    index,
-                                    &deck.value.name,
-                                );
-                            }
-                        });
+                    self.deck_combo_box(ui);
 
                     if ui.button("Create").clicked() {
                         if self.app.create_card() {
                             self.state = GuiState::Editor;
                         }
                     }
+
+                    let added_cards = self.app.added_batch_len();
+                    if added_cards != 0
+                        && ui
+                            .button(format!("Undo {} added cards", added_cards))
+                            .clicked()
+                    {
+                        self.app.undo_last_add_batch();
+                    }
+
+                    if !self.app.decks.is_empty() && ui.button("Import audio folder").clicked() {
+                        let mut dialog =
+                            FileDialog::select_folder(None).default_size(Vec2::new(480.0, 300.0));
+                        dialog.open();
+                        self.import_dialog = Some(dialog);
+                    }
+
+                    if let Some(dialog) = &mut self.import_dialog {
+                        if dialog.show(ctx).selected() {
+                            if let Some(folder) = dialog.path() {
+                                let deck = self.app.current_deck_index();
+                                self.message = Some(
+                                    match self.app.import_audio_folder(folder.as_path(), deck) {
+                                        Ok(count) => format!("Imported {} cards.", count),
+                                        Err(error) => format!("Couldn't import: {}", error),
+                                    },
+                                );
+                            }
+                        }
+                    }
+
+                    if !self.app.decks.is_empty()
+                        && ui
+                            .button("Paste cards")
+                            .on_hover_text("Lines with the front and the back separated by a tab")
+                            .clicked()
+                    {
+                        // egui only gets the clipboard when text is pasted into a widget
+                        let text = arboard::Clipboard::new()
+                            .and_then(|mut clipboard| clipboard.get_text());
+                        self.message = Some(match text {
+                            Ok(text) => {
+                                let deck = self.app.current_deck_index();
+                                let count = self.app.import_tsv_string(&text, deck);
+                                format!("Imported {} cards.", count)
+                            }
+                            Err(error) => format!("Couldn't read the clipboard: {}", error),
+                        });
+                    }
+
+                    if !self.app.decks.is_empty() && ui.button("Import CSV").clicked() {
+                        let mut dialog =
+                            FileDialog::open_file(None).default_size(Vec2::new(480.0, 300.0));
+                        dialog.open();
+                        self.csv_dialog = Some(dialog);
+                    }
+
+                    if let Some(dialog) = &mut self.csv_dialog {
+                        if dialog.show(ctx).selected() {
+                            if let Some(file) = dialog.path() {
+                                let deck = self.app.current_deck_index();
+                                self.message =
+                                    Some(match self.app.import_csv(file.as_path(), deck) {
+                                        Ok(count) => format!("Imported {} cards.", count),
+                                        Err(error) => format!("Couldn't import: {}", error),
+                                    });
+                            }
+                        }
+                    }
                 });
             }
 
             GuiState::Settings => {
                 egui::CentralPanel::default().show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Suggest a break after (0 - never):");
+                        ui.add(egui::DragValue::new(&mut self.app.config.break_after));
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Stop the session time after idle seconds (0 - never):");
+                        ui.add(egui::DragValue::new(&mut self.app.config.idle_timeout_secs));
+                    });
+
+                    egui::ComboBox::from_label("Theme")
+                        .selected_text(match self.app.config.theme {
+                            Theme::System => "System",
+                            Theme::Light => "Light",
+                            Theme::Dark => "Dark",
+                        })
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(
+                                &mut self.app.config.theme,
+                                Theme::System,
+                                "System",
+                            );
+                            ui.selectable_value(&mut self.app.config.theme, Theme::Light, "Light");
+                            ui.selectable_value(&mut self.app.config.theme, Theme::Dark, "Dark");
+                        });
+
+                    if ui
+                        .add(
+                            egui::Slider::new(&mut self.app.config.volume, 0.0..=1.0)
+                                .text("Volume"),
+                        )
+                        .changed()
+                    {
+                        self.app.update_volume();
+                    }
+
+                    ui.checkbox(
+                        &mut self.app.config.autoplay_audio,
+                        "Play audio automatically",
+                    );
+
+                    ui.add_enabled(
+                        self.app.config.autoplay_audio,
+                        egui::Checkbox::new(
+                            &mut self.app.config.replay_front_on_answer,
+                            "Play front audio again with the answer",
+                        ),
+                    );
+
+                    ui.checkbox(
+                        &mut self.app.config.render_markdown,
+                        "Render Markdown on cards",
+                    );
+
+                    ui.checkbox(
+                        &mut self.app.config.watch_folder,
+                        "Reload decks changed by other programs",
+                    );
+
+                    ui.horizontal(|ui| {
+                        ui.label("Hard multiplier:");
+                        ui.add(
+                            egui::DragValue::new(&mut self.app.config.hard_multiplier)
+                                .speed(0.05)
+                                .clamp_range(1.0..=5.0),
+                        );
+                        ui.label("Easy multiplier:");
+                        ui.add(
+                            egui::DragValue::new(&mut self.app.config.easy_multiplier)
+                                .speed(0.05)
+                                .clamp_range(1.0..=5.0),
+                        );
+                        ui.label("Interval after a wrong answer (days):");
+                        ui.add(
+                            egui::DragValue::new(&mut self.app.config.wrong_interval)
+                                .clamp_range(0..=365),
+                        );
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Graduating interval (days):");
+                        ui.add(
+                            egui::DragValue::new(&mut self.app.config.graduating_interval)
+                                .clamp_range(1..=365),
+                        );
+                        ui.label("Easy interval (days):");
+                        ui.add(
+                            egui::DragValue::new(&mut self.app.config.easy_interval)
+                                .clamp_range(1..=365),
+                        );
+                    });
+
+                    egui::ComboBox::from_label("Typed answers")
+                        .selected_text(match self.app.config.answer_strictness {
+                            Strictness::Exact => "Must match exactly",
+                            Strictness::IgnoreCase => "Ignore case",
+                            Strictness::IgnoreWhitespace => "Ignore case and spaces",
+                            Strictness::IgnorePunctuation => "Ignore case, spaces and punctuation",
+                        })
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(
+                                &mut self.app.config.answer_strictness,
+                                Strictness::Exact,
+                                "Must match exactly",
+                            );
+                            ui.selectable_value(
+                                &mut self.app.config.answer_strictness,
+                                Strictness::IgnoreCase,
+                                "Ignore case",
+                            );
+                            ui.selectable_value(
+                                &mut self.app.config.answer_strictness,
+                                Strictness::IgnoreWhitespace,
+                                "Ignore case and spaces",
+                            );
+                            ui.selectable_value(
+                                &mut self.app.config.answer_strictness,
+                                Strictness::IgnorePunctuation,
+                                "Ignore case, spaces and punctuation",
+                            );
+                        });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Wait before grading (ms):");
+                        ui.add(
+                            egui::DragValue::new(&mut self.app.config.min_answer_time_ms).speed(50),
+                        );
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("New cards per day (0 - no limit):");
+                        ui.add(egui::DragValue::new(&mut self.app.config.new_per_day));
+                        ui.label("Reviews per day (0 - no limit):");
+                        ui.add(egui::DragValue::new(&mut self.app.config.reviews_per_day));
+                    });
+
+                    ui.horizontal(|ui| {
+                        let mut shared = self.app.config.global_new_budget.is_some();
+                        if ui
+                            .checkbox(&mut shared, "Limit new cards per day in all decks:")
+                            .changed()
+                        {
+                            self.app.config.global_new_budget = shared.then_some(20);
+                        }
+                        if let Some(budget) = &mut self.app.config.global_new_budget {
+                            ui.add(egui::DragValue::new(budget));
+                        }
+                    });
+                    ui.checkbox(
+                        &mut self.app.config.bury_siblings,
+                        "Bury new siblings of cards reviewed today",
+                    );
+
+                    egui::ComboBox::from_label("Review order")
+                        .selected_text(match self.app.config.review_order {
+                            ReviewOrder::MostOverdue => "Most overdue first",
+                            ReviewOrder::Random => "Random",
+                            ReviewOrder::Added => "Order added",
+                        })
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(
+                                &mut self.app.config.review_order,
+                                ReviewOrder::MostOverdue,
+                                "Most overdue first",
+                            );
+                            ui.selectable_value(
+                                &mut self.app.config.review_order,
+                                ReviewOrder::Random,
+                                "Random",
+                            );
+                            ui.selectable_value(
+                                &mut self.app.config.review_order,
+                                ReviewOrder::Added,
+                                "Order added",
+                            );
+                        });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Characters shown in browser results:");
+                        ui.add(
+                            egui::DragValue::new(&mut self.app.config.preview_length)
+                                .clamp_range(10..=1000),
+                        );
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Suspend cards after this many wrong answers (0 - never):");
+                        ui.add(egui::DragValue::new(&mut self.app.config.leech_threshold));
+                    });
+
+                    ui.horizontal(|ui| {
+                        let label = ui.label("Learning steps in minutes:");
+                        let response = ui
+                            .text_edit_singleline(&mut self.learning_steps)
+                            .labelled_by(label.id)
+                            .on_hover_text("Like \"1 10\", leave empty to skip learning");
+                        if response.changed() {
+                            // Steps are only saved while the text is valid
+                            let steps: std::result::Result<Vec<u64>, _> = self
+                                .learning_steps
+                                .split(|c: char| c == ',' || c.is_whitespace())
+                                .filter(|step| !step.is_empty())
+                                .map(str::parse)
+                                .collect();
+                            if let Ok(steps) = steps {
+                                self.app.config.learning_steps = steps;
+                            }
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Show cards answered wrong again after (minutes):");
+                        ui.add(
+                            egui::DragValue::new(&mut self.app.config.again_interval_mins)
+                                .clamp_range(0..=1440),
+                        );
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Minimum interval in days:");
+                        ui.add(
+                            egui::DragValue::new(&mut self.app.config.min_interval_days)
+                                .clamp_range(1..=365),
+                        );
+                    });
+
+                    if ui.button("Reload decks").clicked() {
+                        self.app.reload();
+                        self.message = self.app.deck_errors();
+                    }
+
                     if ui.button("Change folder with decks").clicked() {
                         let mut dialog =
                             FileDialog::select_folder(None).default_size(Vec2::new(480.0, 300.0));
@@ -290,16 +1733,212 @@ impl eframe::App for GuiApp {
                     if let Some(dialog) = &mut self.file_dialog {
                         if dialog.show(ctx).selected() {
                             if let Some(file) = dialog.path() {
-                                self.app.config.folder_path =
-                                    file.as_path().to_str().unwrap().to_string();
+                                match file.to_str() {
+                                    Some(folder_path) => {
+                                        match self.app.change_folder(folder_path.to_string()) {
+                                            Ok(()) => self.message = self.app.deck_errors(),
+                                            Err(error) => self.message = Some(error),
+                                        }
+                                    }
+                                    None => {
+                                        self.message = Some(format!(
+                                            "{} isn't valid UTF-8, choose another folder",
+                                            file.display()
+                                        ))
+                                    }
+                                }
+                            }
+                        }
+                    }
+
+                    if ui.button("Backup now").clicked() {
+                        let mut dialog =
+                            FileDialog::select_folder(None).default_size(Vec2::new(480.0, 300.0));
+                        dialog.open();
+                        self.backup_dialog = Some(dialog);
+                    }
+
+                    if let Some(dialog) = &mut self.backup_dialog {
+                        if dialog.show(ctx).selected() {
+                            if let Some(folder) = dialog.path() {
+                                self.message = Some(match self.app.backup(folder.as_path()) {
+                                    Ok(path) => format!("Backup saved to {}", path.display()),
+                                    Err(error) => format!("Couldn't back up the decks: {}", error),
+                                });
+                            }
+                        }
+                    }
+
+                    if !self.app.decks.is_empty() {
+                        ui.horizontal(|ui| {
+                            egui::ComboBox::from_label("Format")
+                                .selected_text(match self.export_format {
+                                    ExportFormat::Csv => "CSV",
+                                    ExportFormat::Json => "JSON",
+                                    ExportFormat::CleanBundle => "Zip for sharing",
+                                })
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(
+                                        &mut self.export_format,
+                                        ExportFormat::Csv,
+                                        "CSV",
+                                    );
+                                    ui.selectable_value(
+                                        &mut self.export_format,
+                                        ExportFormat::Json,
+                                        "JSON",
+                                    );
+                                    ui.selectable_value(
+                                        &mut self.export_format,
+                                        ExportFormat::CleanBundle,
+                                        "Zip for sharing",
+                                    )
+                                    .on_hover_text("Cards and media without your reviews");
+                                });
+
+                            if ui
+                                .button(format!("Export {}", self.app.current_deck_name()))
+                                .clicked()
+                            {
+                                let mut dialog = FileDialog::save_file(None)
+                                    .default_size(Vec2::new(480.0, 300.0));
+                                dialog.open();
+                                self.deck_export_dialog = Some(dialog);
+                            }
+                        });
+                    }
+
+                    if let Some(dialog) = &mut self.deck_export_dialog {
+                        if dialog.show(ctx).selected() {
+                            if let Some(file) = dialog.path() {
+                                if let Err(error) = self.app.export_deck(
+                                    self.app.current_deck_index(),
+                                    file.as_path(),
+                                    self.export_format,
+                                ) {
+                                    self.message =
+                                        Some(format!("Couldn't export the deck: {}", error));
+                                }
+                            }
+                        }
+                    }
+                });
+            }
+
+            GuiState::DeckSettings => {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    ui.heading(self.app.current_deck_name());
+
+                    ui.horizontal(|ui| {
+                        let label = ui.label("Name:");
+                        ui.text_edit_singleline(&mut self.deck_name)
+                            .labelled_by(label.id);
+                        if ui.button("Rename").clicked() {
+                            if let Err(error) = self
+                                .app
+                                .rename_deck(self.app.current_deck_index(), self.deck_name.clone())
+                            {
+                                self.message = Some(error);
                             }
                         }
+                    });
+
+                    ui.label("Description:");
+                    if ui.text_edit_multiline(&mut self.deck_description).changed() {
+                        self.app.set_deck_description(
+                            self.app.current_deck_index(),
+                            self.deck_description.clone(),
+                        );
+                    }
+
+                    if ui.button("Check deck health").clicked() {
+                        self.health_report =
+                            Some(self.app.health_report(self.app.current_deck_index()));
+                    }
+                    if let Some(report) = self.health_report.take() {
+                        let fixed = self.health_view(ui, &report);
+                        self.health_report = Some(if fixed {
+                            self.app.health_report(self.app.current_deck_index())
+                        } else {
+                            report
+                        });
+                    }
+
+                    if ui
+                        .button("Compact review history")
+                        .on_hover_text(format!(
+                            "Forgets reviews older than {} days, totals are kept",
+                            COMPACT_KEEP_DAYS
+                        ))
+                        .clicked()
+                    {
+                        let dropped = self
+                            .app
+                            .compact_deck(self.app.current_deck_index(), COMPACT_KEEP_DAYS);
+                        self.message = Some(format!("Removed {} old reviews.", dropped));
+                    }
+
+                    if ui
+                        .button("Rebuild schedules")
+                        .on_hover_text("Schedules cards again from their review history")
+                        .clicked()
+                    {
+                        let rebuilt = self
+                            .app
+                            .rebuild_deck_schedules(self.app.current_deck_index());
+                        self.message = Some(format!("Rebuilt {} schedules.", rebuilt));
+                    }
+
+                    if self.app.decks.len() > 1 {
+                        let source = self.app.current_deck_index();
+                        let mut dest = source;
+                        egui::ComboBox::from_label("Merge into")
+                            .selected_text("Choose a deck")
+                            .show_ui(ui, |ui| {
+                                for (index, deck) in self.app.decks.iter().enumerate() {
+                                    if index != source {
+                                        ui.selectable_value(&mut dest, index, &deck.value.name);
+                                    }
+                                }
+                            });
+                        if dest != source {
+                            self.pending_delete = Some(PendingDelete::Merge { source, dest });
+                        }
+                    }
+                });
+            }
+
+            GuiState::Stats => {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    self.deck_combo_box(ui);
+
+                    if let Some(stats) = self.app.deck_stats(STATS_DAYS) {
+                        ui.label(format!("Due today: {}", stats.due_today));
+                        ui.label(format!("Reviews: {}", stats.total_reviews));
+                        ui.group(|ui| {
+                            ui.label(format!("Last {} days:", STATS_DAYS));
+                            ui.label(format!("Wrong: {}", stats.wrong));
+                            ui.label(format!("Difficult: {}", stats.difficult));
+                            ui.label(format!("Easy: {}", stats.easy));
+                        });
                     }
+
+                    ui.label("Activity in all decks:");
+                    self.activity_heatmap(ui);
                 });
             }
         }
         // Menu
         egui::TopBottomPanel::bottom(Id::new("menu")).show(ctx, |ui| {
+            if let Some(message) = self.message.clone() {
+                ui.horizontal(|ui| {
+                    ui.label(message);
+                    if ui.small_button("OK").clicked() {
+                        self.message = None;
+                    }
+                });
+            }
+
             ui.horizontal(|ui| {
                 if ui.button("Home").clicked() {
                     self.state = GuiState::Main;
@@ -310,6 +1949,9 @@ impl eframe::App for GuiApp {
                 if ui.button("New card").clicked() {
                     self.state = GuiState::NewCard;
                 };
+                if ui.button("Statistics").clicked() {
+                    self.state = GuiState::Stats;
+                };
                 if ui.button("Settings").clicked() {
                     self.state = GuiState::Settings;
                 };
@@ -317,3 +1959,94 @@ impl eframe::App for GuiApp {
         });
     }
 }
+
+/// The typed answer with wrong characters crossed out and missing ones added in green.
+fn typed_answer_diff(ui: &egui::Ui, comparison: &AnswerComparison) -> egui::text::LayoutJob {
+    let mut job = egui::text::LayoutJob::default();
+    let font_id = egui::TextStyle::Heading.resolve(ui.style());
+    for &(c, char_diff) in &comparison.diff {
+        let mut format = egui::TextFormat {
+            font_id: font_id.clone(),
+            color: ui.visuals().text_color(),
+            ..Default::default()
+        };
+        match char_diff {
+            CharDiff::Correct => {}
+            CharDiff::Extra => {
+                format.color = ui.visuals().error_fg_color;
+                format.strikethrough = egui::Stroke::new(1.0, ui.visuals().error_fg_color);
+            }
+            CharDiff::Missing => format.color = egui::Color32::from_rgb(0, 160, 0),
+        }
+        job.append(&c.to_string(), 0.0, format);
+    }
+    job
+}
+
+/// Splits text written in a small part of Markdown into styled pieces:
+/// `**bold**`, `*italics*`, `` `code` `` and lines starting with `- ` or `* ` as list items.
+/// Markers without a closing one on the same line are shown as they are.
+fn markdown_pieces(text: &str) -> Vec<(String, MarkdownStyle)> {
+    let mut pieces = Vec::new();
+    let mut style = MarkdownStyle::default();
+    let mut piece = String::new();
+    for (line_index, line) in text.split('\n').enumerate() {
+        if line_index > 0 {
+            piece.push('\n');
+        }
+
+        let content = line.trim_start();
+        let mut rest = line;
+        if let Some(item) = content
+            .strip_prefix("- ")
+            .or_else(|| content.strip_prefix("* "))
+        {
+            piece.push_str(&line[..line.len() - content.len()]);
+            piece.push_str("• ");
+            rest = item;
+        }
+
+        while let Some(c) = rest.chars().next() {
+            // Nothing is styled inside code
+            let marker = if rest.starts_with('`') {
+                Some("`")
+            } else if style.code {
+                None
+            } else {
+                ["**", "*"]
+                    .into_iter()
+                    .find(|marker| rest.starts_with(marker))
+            };
+            let marker = marker.filter(|&marker| {
+                let open = match marker {
+                    "`" => style.code,
+                    "**" => style.bold,
+                    _ => style.italics,
+                };
+                open || rest[marker.len()..].contains(marker)
+            });
+
+            match marker {
+                Some(marker) => {
+                    if !piece.is_empty() {
+                        pieces.push((std::mem::take(&mut piece), style));
+                    }
+                    match marker {
+                        "`" => style.code = !style.code,
+                        "**" => style.bold = !style.bold,
+                        _ => style.italics = !style.italics,
+                    }
+                    rest = &rest[marker.len()..];
+                }
+                None => {
+                    piece.push(c);
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+        }
+    }
+    if !piece.is_empty() {
+        pieces.push((piece, style));
+    }
+    pieces
+}
@@ -1,41 +1,334 @@
-use rodio::{Decoder, OutputStream, Source};
+use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
 use std::fs::{self, File};
-use std::io::BufReader;
-use std::path::Path;
-use std::thread;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-use smart_learner_core::{card::Card, deck::Deck, field::Field, result::Result};
+use smart_learner_core::{
+    answer::{self, AnswerComparison},
+    card::{Card, CardKind, CardSide, IntervalModifiers, Side},
+    date::{self, Date},
+    deck::{DailyLimits, Deck, DeckStats, DeckStatus, HealthReport, TextDirection},
+    field::Field,
+    result::Result,
+};
 
 use crate::{
-    config::Config,
+    config::{Config, RecentDeck, SavedLapse},
     data::{self, DeckFromFile},
+    export::{self, ExportFormat},
+    import,
+    session::SessionTimer,
+    tts,
+    watcher::FolderWatcher,
 };
 
+/// How many decks are remembered in the "Continue" list.
+const RECENT_DECKS_LIMIT: usize = 5;
+
+/// Files with these extensions are picked up when importing a folder of audio.
+const AUDIO_EXTENSIONS: [&str; 4] = ["mp3", "wav", "ogg", "flac"];
+
+/// How many reviews can be undone.
+const REVIEW_UNDO_LIMIT: usize = 10;
+
+/// A card as it was before a review.
+struct ReviewSnapshot {
+    deck: usize,
+    card_index: usize,
+    card: Card,
+    /// The review added the card to `App::lapses`.
+    added_lapse: bool,
+}
+
+/// Data for the quick stats window shown during revision.
+pub struct RevisionOverlayInfo {
+    /// Current interval of the card in days.
+    pub interval: u64,
+    pub due_cards: usize,
+    pub total_cards: usize,
+}
+
+/// Order of search results in the browser.
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum BrowserSort {
+    /// Best matches first.
+    #[default]
+    None,
+    /// Cards due soonest first, never reviewed cards last.
+    DueDate,
+    /// By front text, ignoring case.
+    Alphabetical,
+    /// Newest cards first.
+    RecentlyAdded,
+}
+
 pub struct App {
     pub config: Config,
+    /// The config file couldn't be loaded and the default config is used.
+    pub config_reset: bool,
     pub decks: Vec<DeckFromFile>,
-    pub current_deck: usize,
+    /// Deck files that couldn't be loaded the last time the folder was read, with the reason.
+    deck_errors: Vec<(PathBuf, String)>,
+    current_deck: usize,
     current_card: Option<usize>,
+    /// Direction the current card is revised in.
+    current_side: Side,
     pub card_front: String,
     pub card_back: String,
+    pub card_kind: CardKind,
+    /// The edited card is studied in both directions.
+    pub card_bidirectional: bool,
+    /// Comma separated tags of the edited card.
+    pub card_tags: String,
+    /// Days until the edited card is due.
+    pub card_due_in: u64,
+    /// Answer typed for the revised card if it's a type answer card.
+    pub typed_answer: String,
+    /// Only cards with this tag are revised, all cards if it's empty.
+    pub revision_tag: String,
     pub search_text: String,
     pub back_search: bool,
+    pub search_all_decks: bool,
+    /// Search tolerates typos.
+    pub fuzzy_search: bool,
+    pub browser_sort: BrowserSort,
+    reviews_since_break: u32,
+    /// Cards due when the deck was opened for revision.
+    session_total: usize,
+    /// Cards reviewed since the deck was opened for revision.
+    session_done: usize,
+    session_timer: SessionTimer,
+    last_autosave: Instant,
+    /// (deck, card, side) answered wrong in the last session of each deck.
+    lapses: Vec<(usize, usize, Side)>,
+    /// Cards of the current deck left in the mistakes review, if one is running.
+    lapse_review: Option<Vec<(usize, Side)>>,
+    folder_watcher: Option<FolderWatcher>,
+    /// (deck, card) pairs added since a deck was last opened for revision.
+    added_batch: Vec<(usize, usize)>,
+    /// Latest reviews, most recent last.
+    review_undo: Vec<ReviewSnapshot>,
+    /// Audio output, nothing is played if it couldn't be opened.
+    audio_output: Option<(OutputStream, OutputStreamHandle)>,
+    /// Clips that are playing now.
+    audio_sink: Option<Sink>,
+    /// Seed of the random revision order, a new one is picked when a deck is opened.
+    review_seed: u64,
+    /// Tag of the cards revised from every deck, None when only the current deck is revised.
+    tag_session: Option<String>,
 }
 
 impl App {
     pub fn new() -> Self {
-        let config: Config = confy::load("smart-learner", None).unwrap();
-        let decks = data::fetch_decks(&Path::new(&config.folder_path));
-        Self {
+        let (config, config_reset) = match confy::load("smart-learner", None) {
+            Ok(config) => (config, false),
+            Err(error) => {
+                log::error!("Can't load the config, using the default one: {}", error);
+                // The defaults are stored on exit, so the broken file is kept next to it
+                if let Ok(path) = confy::get_configuration_file_path("smart-learner", None) {
+                    if let Err(error) = fs::copy(&path, path.with_extension("toml.bak")) {
+                        log::error!("Can't back up {}: {}", path.display(), error);
+                    }
+                }
+                (Config::default(), true)
+            }
+        };
+        let mut app = Self::with_config(config);
+        app.config_reset = config_reset;
+        app
+    }
+
+    /// Loads the decks from the folder in the config.
+    fn with_config(config: Config) -> Self {
+        let (decks, deck_errors) = match data::prepare_folder(Path::new(&config.folder_path)) {
+            Ok(()) => data::fetch_decks(Path::new(&config.folder_path)),
+            Err(error) => {
+                log::error!("{}", error);
+                (Vec::new(), Vec::new())
+            }
+        };
+        // Decks that were deleted or renamed since are forgotten
+        let current_deck = config
+            .last_deck_name
+            .as_ref()
+            .and_then(|name| decks.iter().position(|deck| &deck.value.name == name))
+            .unwrap_or(0);
+        let audio_output = match OutputStream::try_default() {
+            Ok(output) => Some(output),
+            Err(error) => {
+                log::error!("Can't open audio output: {}", error);
+                None
+            }
+        };
+        let mut app = Self {
             config,
+            config_reset: false,
             decks,
-            current_deck: 0,
+            deck_errors,
+            current_deck,
             current_card: None,
+            current_side: Side::Forward,
             card_front: String::new(),
             card_back: String::new(),
+            card_due_in: 0,
+            card_kind: CardKind::default(),
+            typed_answer: String::new(),
+            card_bidirectional: false,
+            card_tags: String::new(),
+            revision_tag: String::new(),
             search_text: String::new(),
             back_search: false,
+            search_all_decks: false,
+            fuzzy_search: false,
+            browser_sort: BrowserSort::default(),
+            reviews_since_break: 0,
+            session_total: 0,
+            session_done: 0,
+            last_autosave: Instant::now(),
+            session_timer: SessionTimer::start(Instant::now()),
+            lapses: Vec::new(),
+            lapse_review: None,
+            tag_session: None,
+            folder_watcher: None,
+            added_batch: Vec::new(),
+            review_undo: Vec::new(),
+            audio_output,
+            audio_sink: None,
+            review_seed: date::current_timestamp() as u64,
+        };
+        app.restore_lapses();
+        app
+    }
+
+    /// Takes today's lapses saved in the config, lapses of missing decks and cards are dropped.
+    fn restore_lapses(&mut self) {
+        let today = Date::current();
+        for lapse in std::mem::take(&mut self.config.lapses) {
+            let deck = self
+                .decks
+                .iter()
+                .position(|deck| deck.value.name == lapse.deck_name);
+            if let Some(deck) = deck.filter(|&deck| {
+                lapse.date == today && lapse.card < self.decks[deck].value.cards.len()
+            }) {
+                self.lapses.push((deck, lapse.card, lapse.side));
+            }
+        }
+    }
+
+    /// Writes all decks to the folder with decks.
+    pub fn save(&mut self) -> std::result::Result<(), Box<dyn Error>> {
+        data::save_decks(&mut self.decks, Path::new(&self.config.folder_path))
+    }
+
+    /// The deck `current_deck` points to, None if there are no decks.
+    pub fn current_deck(&self) -> Option<&DeckFromFile> {
+        self.decks.get(self.current_deck)
+    }
+
+    /// The card that is being revised or edited.
+    fn current_card(&self) -> Option<&Card> {
+        self.current_deck()?.value.cards.get(self.current_card?)
+    }
+
+    pub fn current_deck_index(&self) -> usize {
+        self.current_deck
+    }
+
+    /// Makes the deck current and remembers it, so it's selected again on the next start.
+    pub fn set_current_deck(&mut self, index: usize) {
+        self.current_deck = index;
+        self.remember_current_deck();
+    }
+
+    pub fn current_card_index(&self) -> Option<usize> {
+        self.current_card
+    }
+
+    pub fn current_card_id(&self) -> Option<u64> {
+        self.current_card().map(|card| card.id)
+    }
+
+    /// Describes the deck files that couldn't be loaded, None if every deck was loaded.
+    pub fn deck_errors(&self) -> Option<String> {
+        if self.deck_errors.is_empty() {
+            return None;
+        }
+        let errors: Vec<String> = self
+            .deck_errors
+            .iter()
+            .map(|(path, error)| format!("{}: {}", path.display(), error))
+            .collect();
+        Some(format!(
+            "Some decks couldn't be loaded. {}",
+            errors.join(", ")
+        ))
+    }
+
+    pub fn check_folder(&self) -> std::result::Result<(), String> {
+        data::prepare_folder(Path::new(&self.config.folder_path))
+    }
+
+    /// Switches to another folder with decks and loads them, refusing paths that can't hold them.
+    pub fn change_folder(&mut self, folder_path: String) -> std::result::Result<(), String> {
+        data::prepare_folder(Path::new(&folder_path))?;
+        self.config.folder_path = folder_path;
+        // Recreated for the new folder by `process_folder_events`
+        self.folder_watcher = None;
+        self.load_decks();
+        Ok(())
+    }
+
+    /// Loads the decks from disk again, the current deck stays selected if it's still there.
+    /// Unsaved changes are written first, so they win over changes made by other programs.
+    /// Decks whose files can't be loaded anymore are kept as they are in memory.
+    pub fn reload(&mut self) {
+        for deck in self.decks.iter_mut().filter(|deck| deck.dirty) {
+            log::warn!(
+                "{} has unsaved changes, saving them before reloading",
+                deck.path
+            );
+            deck.save();
+        }
+
+        let current_deck_path = self.current_deck().map(|deck| deck.path.clone());
+        let old_decks = std::mem::take(&mut self.decks);
+        self.load_decks();
+        for deck in old_decks {
+            if self
+                .deck_errors
+                .iter()
+                .any(|(path, _)| path == Path::new(&deck.path))
+            {
+                log::warn!("Keeping {} as it was before reloading", deck.path);
+                self.decks.push(deck);
+            }
         }
+        if let Some(index) =
+            current_deck_path.and_then(|path| self.decks.iter().position(|deck| deck.path == path))
+        {
+            self.set_current_deck(index);
+        }
+    }
+
+    /// Loads decks from `config.folder_path`, forgetting state that points into the old ones.
+    fn load_decks(&mut self) {
+        // Old decks are saved when dropped, before the files are read
+        self.decks.clear();
+        (self.decks, self.deck_errors) = data::fetch_decks(Path::new(&self.config.folder_path));
+        self.set_current_deck(0);
+        self.current_card = None;
+        self.lapses.clear();
+        self.lapse_review = None;
+        self.added_batch.clear();
+        self.review_undo.clear();
+        self.session_total = 0;
+        self.session_done = 0;
     }
 
     pub fn new_deck(&mut self, deck_name: String) {
@@ -44,81 +337,538 @@ impl App {
         self.decks.push(DeckFromFile {
             value: Deck::new(deck_name),
             path: path.as_path().to_str().unwrap().to_string() + ".sdeck",
+            dirty: true,
         });
+        self.decks.last_mut().unwrap().save();
+        // The first deck becomes current
+        self.remember_current_deck();
+    }
+
+    /// Stores the name of the current deck, so it's selected again on the next start.
+    fn remember_current_deck(&mut self) {
+        self.config.last_deck_name = self.current_deck().map(|deck| deck.value.name.clone());
+    }
+
+    /// Renames the deck together with its file, refusing names of existing deck files.
+    pub fn rename_deck(
+        &mut self,
+        index: usize,
+        new_name: String,
+    ) -> std::result::Result<(), String> {
+        check_deck_name(&new_name)?;
+        let path = Path::new(&self.config.folder_path)
+            .join(&new_name)
+            .to_str()
+            .unwrap()
+            .to_string()
+            + ".sdeck";
+        if path == self.decks[index].path {
+            return Ok(());
+        }
+        if Path::new(&path).exists() {
+            return Err(format!("A deck file named {} already exists", new_name));
+        }
+
+        let deck = &mut self.decks[index];
+        fs::rename(&deck.path, &path)
+            .map_err(|error| format!("Can't rename {}: {}", deck.path, error))?;
+        for recent in &mut self.config.recent_decks {
+            if recent.name == deck.value.name {
+                recent.name = new_name.clone();
+            }
+        }
+        deck.value.name = new_name;
+        deck.path = path;
+        deck.dirty = true;
+        self.remember_current_deck();
+        Ok(())
+    }
+
+    /// Removes the deck and its file. A revision of the deck is stopped.
+    pub fn delete_deck(&mut self, index: usize) -> io::Result<()> {
+        let name = self.decks[index].value.name.clone();
+        let path = self.decks[index].path.clone();
+        // The deck is saved when dropped, so the file is removed afterwards
+        self.decks.remove(index);
+        fs::remove_file(path)?;
+
+        self.config
+            .recent_decks
+            .retain(|recent| recent.name != name);
+        self.lapses.retain(|&(deck, _, _)| deck != index);
+        self.added_batch.retain(|&(deck, _)| deck != index);
+        let lapse_decks = self.lapses.iter_mut().map(|(deck, _, _)| deck);
+        for deck in lapse_decks.chain(self.added_batch.iter_mut().map(|(deck, _)| deck)) {
+            if *deck > index {
+                *deck -= 1;
+            }
+        }
+        self.review_undo.retain(|snapshot| snapshot.deck != index);
+        for snapshot in &mut self.review_undo {
+            if snapshot.deck > index {
+                snapshot.deck -= 1;
+            }
+        }
+
+        if index == self.current_deck {
+            self.current_card = None;
+            self.lapse_review = None;
+        }
+        if self.current_deck > index || self.current_deck >= self.decks.len() {
+            self.set_current_deck(self.current_deck.saturating_sub(1));
+        } else {
+            self.remember_current_deck();
+        }
+        Ok(())
+    }
+
+    /// Appends the cards of `source` to `dest` with their scheduling and deletes `source`.
+    /// If `source` was current, `dest` becomes current.
+    pub fn merge_decks(&mut self, source: usize, dest: usize) -> io::Result<()> {
+        if source == dest {
+            return Ok(());
+        }
+
+        let offset = self.decks[dest].value.cards.len();
+        let cards = std::mem::take(&mut self.decks[source].value.cards);
+        for card in cards {
+            self.decks[dest].value.add_card(card);
+        }
+        self.decks[dest].dirty = true;
+        self.decks[dest].save();
+
+        // Cards keep being tracked in their new deck
+        let lapse_cards = self.lapses.iter_mut().map(|(deck, index, _)| (deck, index));
+        let added_cards = self
+            .added_batch
+            .iter_mut()
+            .map(|(deck, index)| (deck, index));
+        for (deck, index) in lapse_cards.chain(added_cards) {
+            if *deck == source {
+                *deck = dest;
+                *index += offset;
+            }
+        }
+        for snapshot in self.review_undo.iter_mut().filter(|s| s.deck == source) {
+            snapshot.deck = dest;
+            snapshot.card_index += offset;
+        }
+
+        let was_current = self.current_deck == source;
+        self.delete_deck(source)?;
+        if was_current {
+            self.set_current_deck(if dest > source { dest - 1 } else { dest });
+        }
+        Ok(())
+    }
+
+    /// Makes the deck current and moves it to the front of the recent decks list.
+    pub fn open_deck(&mut self, index: usize) {
+        self.set_current_deck(index);
+        // A card left from editing would be served before the deck's due cards
+        self.current_card = None;
+        self.tag_session = None;
+        self.added_batch.clear();
+        self.session_total = self.due_count();
+        self.session_done = 0;
+        self.session_timer = SessionTimer::start(Instant::now());
+        self.review_seed = date::current_timestamp() as u64;
+
+        let name = self.decks[index].value.name.clone();
+        self.config
+            .recent_decks
+            .retain(|recent| recent.name != name);
+        self.config.recent_decks.insert(
+            0,
+            RecentDeck {
+                name,
+                opened: Date::current(),
+            },
+        );
+        self.config.recent_decks.truncate(RECENT_DECKS_LIMIT);
+    }
+
+    /// Returns indices of recently studied decks, most recent first.
+    pub fn recent_decks(&self) -> Vec<usize> {
+        self.config
+            .recent_decks
+            .iter()
+            .filter_map(|recent| {
+                self.decks
+                    .iter()
+                    .position(|deck| deck.value.name == recent.name)
+            })
+            .collect()
+    }
+
+    /// Returns the next deck after the current one that has cards to review.
+    pub fn next_due_deck(&self) -> Option<usize> {
+        (1..=self.decks.len())
+            .map(|offset| (self.current_deck + offset) % self.decks.len())
+            .find(|&index| {
+                self.decks[index]
+                    .value
+                    .due_card(
+                        &self.daily_limits(),
+                        self.config.review_order,
+                        self.review_seed,
+                    )
+                    .is_some()
+            })
+    }
+
+    /// Opens the next deck with due cards, returns false if there is none.
+    pub fn switch_to_next_due_deck(&mut self) -> bool {
+        match self.next_due_deck() {
+            Some(index) => {
+                self.open_deck(index);
+                self.lapse_review = None;
+                true
+            }
+            None => false,
+        }
     }
 
     /// Returns (card_exists, got a new card).
     pub fn get_card_for_revision(&mut self) -> (bool, bool) {
-        self.current_card = match self.current_card {
-            Some(result) => {
-                if self.decks[self.current_deck].value.cards[result].current_repeat_in == 0 {
-                    return (true, false);
-                } else {
-                    self.decks[self.current_deck].value.due_card()
+        if let Some(queue) = &self.lapse_review {
+            return match queue.first().copied() {
+                Some((card_index, side)) => {
+                    let new_card =
+                        self.current_card != Some(card_index) || self.current_side != side;
+                    if new_card {
+                        self.change_card(card_index);
+                        self.current_side = side;
+                    }
+                    (true, new_card)
                 }
-            }
-            None => self.decks[self.current_deck].value.due_card(),
+                None => {
+                    self.lapse_review = None;
+                    (false, false)
+                }
+            };
+        }
+
+        if self.current_deck().is_none() {
+            return (false, false);
+        }
+        // The other side of the card counts as a new card
+        if let Some(side) = self.current_card().and_then(Card::due_side) {
+            let new_side = side != self.current_side;
+            self.current_side = side;
+            return (true, new_side);
+        }
+
+        let limits = self.daily_limits();
+        let due_card = |deck: &DeckFromFile, tag: Option<&str>| {
+            deck.value
+                .due_card_with_tag(tag, &limits, self.config.review_order, self.review_seed)
+        };
+        let next = match &self.tag_session {
+            // Decks are revised one after another, every card is graded in its own deck
+            Some(tag) => self
+                .decks
+                .iter()
+                .enumerate()
+                .find_map(|(deck_index, deck)| {
+                    due_card(deck, Some(tag)).map(|card_index| (deck_index, card_index))
+                }),
+            None => due_card(&self.decks[self.current_deck], self.revision_tag_filter())
+                .map(|card_index| (self.current_deck, card_index)),
         };
 
-        if self.current_card.is_some() {
-            self.change_card(self.current_card.unwrap());
-            (true, true)
-        } else {
-            (false, false)
+        match next {
+            Some((deck_index, card_index)) => {
+                self.set_current_deck(deck_index);
+                self.change_card(card_index);
+                self.current_side = self
+                    .current_card()
+                    .and_then(Card::due_side)
+                    .unwrap_or_default();
+                (true, true)
+            }
+            None => {
+                self.current_card = None;
+                (false, false)
+            }
+        }
+    }
+
+    /// Tag of the revised cards, from the tag session or typed on the main page.
+    fn revision_tag_filter(&self) -> Option<&str> {
+        self.tag_session
+            .as_deref()
+            .or(Some(self.revision_tag.trim()).filter(|tag| !tag.is_empty()))
+    }
+
+    /// Revises the cards with the tag from every deck until a deck is opened.
+    pub fn start_tag_session(&mut self, tag: String) {
+        self.tag_session = Some(tag);
+        self.session_timer = SessionTimer::start(Instant::now());
+        self.current_card = None;
+        self.lapse_review = None;
+    }
+
+    /// Tag revised from every deck, if a tag session is running.
+    pub fn tag_session(&self) -> Option<&str> {
+        self.tag_session.as_deref()
+    }
+
+    fn daily_limits(&self) -> DailyLimits {
+        DailyLimits {
+            new_cards: self.config.new_per_day,
+            reviews: self.config.reviews_per_day,
+            new_budget: self
+                .config
+                .global_new_budget
+                .map(|budget| budget.saturating_sub(self.new_cards_today())),
+            buried_notes: if self.config.bury_siblings {
+                self.decks
+                    .iter()
+                    .flat_map(|deck| deck.value.notes_reviewed_today())
+                    .collect()
+            } else {
+                HashSet::new()
+            },
+        }
+    }
+
+    /// Cards first reviewed today in all decks.
+    fn new_cards_today(&self) -> usize {
+        self.decks
+            .iter()
+            .map(|deck| deck.value.reviewed_today().0 as usize)
+            .sum()
+    }
+
+    pub fn revision_overlay_info(&self) -> Option<RevisionOverlayInfo> {
+        let deck = &self.current_deck()?.value;
+        let card = self.current_card()?;
+        Some(RevisionOverlayInfo {
+            interval: card.schedule(self.current_side).repeat_in,
+            due_cards: deck.due_count(),
+            total_cards: deck.cards.len(),
+        })
+    }
+
+    /// Learning step of the revised side and how many steps there are, see
+    /// `Card::learning_progress`.
+    pub fn learning_progress(&self) -> Option<(usize, usize)> {
+        self.current_card()?
+            .learning_progress(self.current_side, self.config.learning_steps.len())
+    }
+
+    /// Cards of the current deck that are due now.
+    pub fn due_count(&self) -> usize {
+        match self.current_deck() {
+            Some(deck) => deck.value.due_count(),
+            None => 0,
+        }
+    }
+
+    /// Called on user input during revision, see `SessionTimer`.
+    pub fn record_activity(&mut self) {
+        self.session_timer
+            .activity(Instant::now(), self.idle_timeout());
+    }
+
+    /// Time spent studying since the revision session started.
+    pub fn session_elapsed(&self) -> Duration {
+        self.session_timer
+            .elapsed(Instant::now(), self.idle_timeout())
+    }
+
+    fn idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.config.idle_timeout_secs)
+    }
+
+    /// Reviewed and total cards of the revision session.
+    /// The total grows if more cards become due, like the other side of a bidirectional card.
+    pub fn session_progress(&self) -> (usize, usize) {
+        let total = self.session_total.max(self.session_done + self.due_count());
+        (self.session_done, total)
+    }
+
+    /// Fronts of the next cards in the current deck's revision queue.
+    pub fn upcoming_cards(&self, n: usize) -> Vec<String> {
+        match self.current_deck() {
+            Some(deck) => deck
+                .value
+                .upcoming(
+                    n,
+                    self.revision_tag_filter(),
+                    &self.daily_limits(),
+                    self.config.review_order,
+                    self.review_seed,
+                )
+                .into_iter()
+                .map(|card_index| deck.value.cards[card_index].front.text.clone())
+                .collect(),
+            None => Vec::new(),
         }
     }
 
     pub fn get_answer(&self) -> String {
-        if self.current_card.is_some() {
-            self.decks[self.current_deck].value.cards[self.current_card.unwrap()]
-                .back
-                .text
-                .clone()
-        } else {
-            "".to_string()
+        match self.current_card() {
+            Some(card) => card.answer(self.current_side),
+            None => "".to_string(),
+        }
+    }
+
+    /// The answer to the revised card has to be typed.
+    pub fn asks_typed_answer(&self) -> bool {
+        self.current_card()
+            .is_some_and(|card| card.kind == CardKind::TypeAnswer)
+    }
+
+    /// Compares `typed_answer` with the answer, None if the card isn't a type answer card.
+    pub fn compare_typed_answer(&self) -> Option<AnswerComparison> {
+        if !self.asks_typed_answer() {
+            return None;
+        }
+        Some(answer::compare_answer(
+            &self.get_answer(),
+            &self.typed_answer,
+            self.config.answer_strictness,
+        ))
+    }
+
+    /// Pieces of the answer, true marks the ones hidden in the question.
+    pub fn get_answer_parts(&self) -> Vec<(String, bool)> {
+        match self.current_card() {
+            Some(card) => card.answer_parts(self.current_side),
+            None => Vec::new(),
         }
     }
 
     pub fn get_question(&self) -> String {
-        if self.current_card.is_some() {
-            self.decks[self.current_deck].value.cards[self.current_card.unwrap()]
-                .front
-                .text
-                .clone()
-        } else {
-            "".to_string()
+        match self.current_card() {
+            Some(card) => card.question(self.current_side),
+            None => "".to_string(),
+        }
+    }
+
+    pub fn deck_status(&self, index: usize) -> DeckStatus {
+        match self.decks.get(index) {
+            Some(deck) => deck.value.status(&self.daily_limits()),
+            None => DeckStatus::NoCards,
+        }
+    }
+
+    pub fn deck_stats(&self, days: u64) -> Option<DeckStats> {
+        Some(self.current_deck()?.value.stats(days))
+    }
+
+    /// Reviews in all decks on each of the last `days` days, oldest day first.
+    pub fn activity_by_day(&self, days: u64) -> Vec<(Date, u32)> {
+        let today = Date::current();
+        let mut counts = vec![0; days as usize];
+        let review_dates = self
+            .decks
+            .iter()
+            .flat_map(|deck| &deck.value.cards)
+            .flat_map(|card| &card.history)
+            .map(|(date, _)| date);
+        for date in review_dates {
+            if *date > today {
+                continue;
+            }
+            let days_ago = date.difference(&today);
+            if days_ago < days {
+                counts[(days - 1 - days_ago) as usize] += 1;
+            }
+        }
+
+        counts
+            .into_iter()
+            .enumerate()
+            .map(|(index, count)| (today.sub_days(days - 1 - index as u64), count))
+            .collect()
+    }
+
+    pub fn text_direction(&self) -> TextDirection {
+        match self.current_deck() {
+            Some(deck) => deck.value.text_direction,
+            None => TextDirection::default(),
         }
     }
 
+    pub fn set_text_direction(&mut self, text_direction: TextDirection) {
+        let deck = &mut self.decks[self.current_deck];
+        deck.value.text_direction = text_direction;
+        deck.dirty = true;
+        deck.save();
+    }
+
+    pub fn set_deck_description(&mut self, index: usize, description: String) {
+        let deck = &mut self.decks[index];
+        deck.value.description = description;
+        deck.dirty = true;
+        deck.save();
+    }
+
+    /// Drops old reviews from the deck, see `Deck::compact`. Returns how many were dropped.
+    pub fn compact_deck(&mut self, index: usize, keep_log_days: u32) -> usize {
+        let dropped = self.decks[index].value.compact(keep_log_days);
+        if dropped > 0 {
+            self.decks[index].dirty = true;
+            self.decks[index].save();
+        }
+        dropped
+    }
+
     pub fn current_deck_name(&self) -> String {
-        if self.decks.len() > self.current_deck {
-            self.decks[self.current_deck].value.name.clone()
-        } else {
-            "No decks".to_string()
+        match self.current_deck() {
+            Some(deck) => deck.value.name.clone(),
+            None => "No decks".to_string(),
         }
     }
 
     pub fn create_card(&mut self) -> bool {
-        if self.decks.len() == 0 {
+        if self.current_deck().is_none() {
             self.current_card = None;
             return false;
         }
 
-        self.decks[self.current_deck].value.cards.push(Card::new(
+        let new_card = self.decks[self.current_deck].value.add_card(Card::new(
             Field {
                 text: "New front".to_string(),
                 audio_path: None,
+                image_path: None,
+                image_after_answer: false,
             },
             Field {
                 text: "New back".to_string(),
                 audio_path: None,
+                image_path: None,
+                image_after_answer: false,
             },
         ));
-        self.change_card(self.decks[self.current_deck].value.cards.len() - 1);
+        self.decks[self.current_deck].dirty = true;
+        self.added_batch.push((self.current_deck, new_card));
+        self.change_card(new_card);
         true
     }
 
-    pub fn edit_card(&mut self) {
+    /// Adds a copy of the card with a fresh schedule to the current deck and makes it current.
+    /// The copy is a sibling of the card, it can be moved to another deck.
+    pub fn create_similar_card(&mut self, card_index: usize) {
+        let deck = &mut self.decks[self.current_deck].value;
+        deck.cards[card_index].note_id();
+        let card = deck.cards[card_index].similar();
+        let new_card = deck.add_card(card);
+        self.decks[self.current_deck].dirty = true;
+        self.added_batch.push((self.current_deck, new_card));
+        self.change_card(new_card);
+    }
+
+    /// Writes the edited fields to the current card.
+    /// Returns false and changes nothing if both the front and the back are empty.
+    pub fn edit_card(&mut self) -> bool {
+        if self.card_front.trim().is_empty() && self.card_back.trim().is_empty() {
+            return false;
+        }
+
         self.decks[self.current_deck].value.cards[self.current_card.unwrap()]
             .front
             .text = self.card_front.clone();
@@ -126,16 +876,185 @@ impl App {
         self.decks[self.current_deck].value.cards[self.current_card.unwrap()]
             .back
             .text = self.card_back.clone();
+
+        self.decks[self.current_deck].value.cards[self.current_card.unwrap()].kind = self.card_kind;
+
+        self.decks[self.current_deck].value.cards[self.current_card.unwrap()].bidirectional =
+            self.card_bidirectional;
+
+        self.decks[self.current_deck].value.cards[self.current_card.unwrap()].tags = self
+            .card_tags
+            .split(',')
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        self.decks[self.current_deck].dirty = true;
+        self.save_current_deck();
+        true
     }
 
-    pub fn search(&mut self) -> Vec<(usize, String)> {
-        if self.decks.len() == 0 {
+    /// Searches the current deck, returns (card, front preview, back preview) for each match.
+    pub fn search(&mut self) -> Vec<(usize, String, String)> {
+        let Some(deck) = self.current_deck() else {
             return Vec::new();
+        };
+        let mut results: Vec<(usize, String, String)> = self
+            .search_deck(&deck.value)
+            .into_iter()
+            .map(|card_index| {
+                let (front, back) = self.previews(&deck.value.cards[card_index]);
+                (card_index, front, back)
+            })
+            .collect();
+        self.sort_results(&mut results, |(card_index, _, _)| {
+            (*card_index, &deck.value.cards[*card_index])
+        });
+        results
+    }
+
+    /// Indices of the cards in the deck that match the search, best matches first.
+    /// "id:N" finds only the card with the id N.
+    fn search_deck(&self, deck: &Deck) -> Vec<usize> {
+        if let Some(id) = self.search_text.trim().strip_prefix("id:") {
+            return match id.trim().parse() {
+                Ok(id) => deck
+                    .card_by_id(id)
+                    .map(|(card_index, _)| card_index)
+                    .into_iter()
+                    .collect(),
+                Err(_) => Vec::new(),
+            };
         }
 
-        self.decks[self.current_deck]
-            .value
-            .search(self.back_search, self.search_text.clone())
+        if self.fuzzy_search {
+            deck.fuzzy_search(self.back_search, self.search_text.clone())
+                .into_iter()
+                .map(|(card_index, _, _)| card_index)
+                .collect()
+        } else {
+            deck.search(self.back_search, self.search_text.clone())
+                .into_iter()
+                .map(|(card_index, _)| card_index)
+                .collect()
+        }
+    }
+
+    /// Front and back of the card, shortened to `Config::preview_length` characters.
+    fn previews(&self, card: &Card) -> (String, String) {
+        let length = self.config.preview_length;
+        (
+            truncate(&card.front.text, length),
+            truncate(&card.back.text, length),
+        )
+    }
+
+    /// Searches every deck, returns (deck, card, front preview, back preview) for each match.
+    pub fn search_all(&self) -> Vec<(usize, usize, String, String)> {
+        let mut results: Vec<(usize, usize, String, String)> = self
+            .decks
+            .iter()
+            .enumerate()
+            .flat_map(|(deck_index, deck)| {
+                self.search_deck(&deck.value)
+                    .into_iter()
+                    .map(move |card_index| {
+                        let (front, back) = self.previews(&deck.value.cards[card_index]);
+                        (deck_index, card_index, front, back)
+                    })
+            })
+            .collect();
+        self.sort_results(&mut results, |(deck_index, card_index, _, _)| {
+            (
+                *card_index,
+                &self.decks[*deck_index].value.cards[*card_index],
+            )
+        });
+        results
+    }
+
+    /// Adds the tag to every card the browser search finds, cards that have it are left alone.
+    pub fn tag_search_results(&mut self, tag: String) {
+        let tag = tag.trim().to_string();
+        if tag.is_empty() {
+            return;
+        }
+
+        let results: Vec<(usize, usize)> = if self.search_all_decks {
+            self.search_all()
+                .into_iter()
+                .map(|(deck_index, card_index, _, _)| (deck_index, card_index))
+                .collect()
+        } else {
+            let deck_index = self.current_deck;
+            self.search()
+                .into_iter()
+                .map(|(card_index, _, _)| (deck_index, card_index))
+                .collect()
+        };
+
+        for (deck_index, card_index) in results {
+            let deck = &mut self.decks[deck_index];
+            let card = &mut deck.value.cards[card_index];
+            if !card.has_tag(&tag) {
+                card.tags.push(tag.clone());
+                deck.dirty = true;
+            }
+        }
+        for deck in self.decks.iter_mut().filter(|deck| deck.dirty) {
+            deck.save();
+        }
+    }
+
+    /// Orders search results by `browser_sort`, `card` gives the index and card of a result.
+    fn sort_results<'a, T>(&self, results: &mut [T], card: impl Fn(&T) -> (usize, &'a Card)) {
+        match self.browser_sort {
+            BrowserSort::None => {}
+            BrowserSort::DueDate => results.sort_by_cached_key(|result| {
+                let (_, card) = card(result);
+                (card.is_new(), card.next_due())
+            }),
+            BrowserSort::Alphabetical => {
+                results.sort_by_cached_key(|result| card(result).1.front.text.to_lowercase())
+            }
+            // Cards are appended, so newer cards have higher indices
+            BrowserSort::RecentlyAdded => {
+                results.sort_by_key(|result| std::cmp::Reverse(card(result).0))
+            }
+        }
+    }
+
+    /// Zips the decks with their audio and images into a timestamped file in `dest`,
+    /// returns the path of the backup. Unsaved changes are saved first.
+    pub fn backup(&mut self, dest: &Path) -> std::result::Result<PathBuf, Box<dyn Error>> {
+        self.save()?;
+        let path = dest.join(format!(
+            "backup-{}.zip",
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        ));
+        export::folder_backup(Path::new(&self.config.folder_path), &path)?;
+        Ok(path)
+    }
+
+    pub fn export_pdf(
+        &self,
+        deck_index: usize,
+        path: &Path,
+    ) -> std::result::Result<(), Box<dyn Error>> {
+        export::deck_to_pdf(&self.decks[deck_index].value, path)
+    }
+
+    pub fn export_deck(
+        &self,
+        deck_index: usize,
+        path: &Path,
+        format: ExportFormat,
+    ) -> std::result::Result<(), Box<dyn Error>> {
+        export::export_deck(
+            &self.decks[deck_index].value,
+            Path::new(&self.config.folder_path),
+            path,
+            format,
+        )
     }
 
     pub fn change_card(&mut self, card_index: usize) {
@@ -143,112 +1062,1774 @@ impl App {
         let card = &self.decks[self.current_deck].value.cards[self.current_card.unwrap()];
         self.card_front = card.front.text.clone();
         self.card_back = card.back.text.clone();
+        self.card_kind = card.kind;
+        self.card_bidirectional = card.bidirectional;
+        self.card_tags = card.tags.join(", ");
+        self.card_due_in = card.schedule.current_repeat_in;
+        self.current_side = Side::Forward;
+        self.typed_answer.clear();
     }
 
-    pub fn card_revised(&mut self, result: Result) {
-        self.decks[self.current_deck].value.cards[self.current_card.unwrap()].review(result);
+    pub fn is_card_suspended(&self) -> bool {
+        self.current_card().is_some_and(|card| card.suspended)
     }
 
-    pub fn delete_card(&mut self) {
-        self.decks[self.current_deck]
-            .value
-            .cards
-            .swap_remove(self.current_card.unwrap());
-        self.current_card = None;
+    /// Suspends the current card, or brings it back to revision if it's suspended.
+    pub fn toggle_suspended(&mut self) {
+        let card = &mut self.decks[self.current_deck].value.cards[self.current_card.unwrap()];
+        card.suspended = !card.suspended;
+        self.decks[self.current_deck].dirty = true;
+        self.save_current_deck();
     }
 
-    fn play_audio(&self, path: String) {
-        let path = Path::new(&self.config.folder_path)
-            .to_path_buf()
-            .join(Path::new("audio"))
-            .join(Path::new(&path));
+    /// Sets when the current card is due, in both directions if it has two.
+    pub fn reschedule_card(&mut self, days: u64) {
+        let card = &mut self.decks[self.current_deck].value.cards[self.current_card.unwrap()];
+        for side in card.sides().collect::<Vec<_>>() {
+            card.schedule_mut(side).reschedule(days);
+        }
+        self.decks[self.current_deck].dirty = true;
+        self.save_current_deck();
+    }
+
+    pub fn card_revised(&mut self, result: Result) {
+        let card_index = self.current_card.unwrap();
+        self.reviews_since_break += 1;
 
-        thread::spawn(|| {
-            let file = BufReader::new(File::open(path).unwrap());
-            let source = Decoder::new(file).unwrap();
+        // Mistakes are only repeated, they don't affect scheduling
+        if let Some(queue) = &mut self.lapse_review {
+            let side = self.current_side;
+            queue.retain(|&lapse| lapse != (card_index, side));
+            return;
+        }
 
-            let (_stream, stream_handle) = OutputStream::try_default().unwrap();
+        // Mistakes of the deck's last session are kept until a new session starts reviewing
+        if self.session_done == 0 {
+            let deck_index = self.current_deck;
+            self.lapses.retain(|&(deck, _, _)| deck != deck_index);
+        }
+        let lapse = (self.current_deck, card_index, self.current_side);
+        let added_lapse = matches!(result, Result::Wrong) && !self.lapses.contains(&lapse);
+        if added_lapse {
+            self.lapses.push(lapse);
+        }
 
-            stream_handle.play_raw(source.convert_samples()).unwrap();
-            std::thread::sleep(std::time::Duration::from_secs(5));
+        self.session_done += 1;
+        let modifiers = self.interval_modifiers();
+        let card = &mut self.decks[self.current_deck].value.cards[card_index];
+        if self.review_undo.len() == REVIEW_UNDO_LIMIT {
+            self.review_undo.remove(0);
+        }
+        self.review_undo.push(ReviewSnapshot {
+            deck: self.current_deck,
+            card_index,
+            card: card.clone(),
+            added_lapse,
         });
-    }
 
-    pub fn play_front_audio(&self) {
-        let card = &self.decks[self.current_deck].value.cards[self.current_card.unwrap()];
-        if card.front.audio_path.is_some() {
-            self.play_audio(card.front.audio_path.clone().unwrap());
+        card.review(self.current_side, result, &modifiers);
+        card.schedule_mut(self.current_side)
+            .apply_min_interval(self.config.min_interval_days);
+        if card.check_leech(self.config.leech_threshold) {
+            log::info!("Card {} became a leech and was suspended", card_index);
         }
+        self.decks[self.current_deck].dirty = true;
+        self.save_current_deck();
     }
 
-    pub fn play_back_audio(&self) {
-        let card = &self.decks[self.current_deck].value.cards[self.current_card.unwrap()];
-        if card.back.audio_path.is_some() {
-            self.play_audio(card.back.audio_path.clone().unwrap());
+    fn interval_modifiers(&self) -> IntervalModifiers {
+        IntervalModifiers {
+            // The config file can be edited by hand, intervals shouldn't shrink
+            hard_multiplier: self.config.hard_multiplier.max(1.0),
+            easy_multiplier: self.config.easy_multiplier.max(1.0),
+            wrong_interval: self.config.wrong_interval,
+            again_interval_mins: self.config.again_interval_mins,
+            graduating_interval: self.config.graduating_interval,
+            easy_interval: self.config.easy_interval,
+            learning_steps: self.config.learning_steps.clone(),
         }
     }
 
-    fn get_audio_file(&mut self, path: String) {
-        // Getting a file name
-        let old_file_name = Path::new(&path)
-            .file_name()
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .to_string();
-
-        // Audio folder in folder with decks
-        let audio_path_stem = Path::new(&self.config.folder_path).join("audio");
+    /// Rebuilds the schedules of the deck's cards from their history, see
+    /// `Deck::rebuild_schedules`. Returns how many cards were rebuilt.
+    pub fn rebuild_deck_schedules(&mut self, index: usize) -> usize {
+        let modifiers = self.interval_modifiers();
+        let rebuilt = self.decks[index]
+            .value
+            .rebuild_schedules(&modifiers, self.config.min_interval_days);
+        if rebuilt > 0 {
+            self.decks[index].dirty = true;
+            self.decks[index].save();
+        }
+        rebuilt
+    }
 
-        // Path to a audio folder and old filename
-        let mut new_file_path = audio_path_stem.join(old_file_name.clone());
+    pub fn can_undo_review(&self) -> bool {
+        !self.review_undo.is_empty()
+    }
 
-        // Change filename if it exists
-        if audio_path_stem.join(&old_file_name).exists() {
-            for i in 1.. {
-                let clone = old_file_name.clone();
-                let mut path_iter = clone.split('.');
-                let file_extention = ".".to_owned() + path_iter.next_back().unwrap();
-                let file_name = path_iter.next_back().unwrap();
-                let file_name_string = format!("{}{}{}", file_name, i.to_string(), file_extention);
-                let file_name_path = Path::new(&file_name_string);
-                new_file_path = audio_path_stem.join(file_name_path);
+    /// Restores the card from before the last review and makes it current again.
+    pub fn undo_last_review(&mut self) {
+        let Some(snapshot) = self.review_undo.pop() else {
+            return;
+        };
 
-                if !new_file_path.exists() {
-                    break;
-                }
-            }
+        let card = &mut self.decks[snapshot.deck].value.cards[snapshot.card_index];
+        // Merging decks gives the card a new id, which has to stay unique
+        let id = card.id;
+        *card = snapshot.card;
+        card.id = id;
+        self.decks[snapshot.deck].dirty = true;
+        if snapshot.added_lapse {
+            self.lapses.retain(|&(deck, card_index, _)| {
+                (deck, card_index) != (snapshot.deck, snapshot.card_index)
+            });
         }
+        self.reviews_since_break = self.reviews_since_break.saturating_sub(1);
+        self.session_done = self.session_done.saturating_sub(1);
 
-        // Copy a file to the local folder
-        fs::copy(path.clone(), new_file_path).unwrap();
+        self.set_current_deck(snapshot.deck);
+        self.lapse_review = None;
+        self.change_card(snapshot.card_index);
+        self.save_current_deck();
     }
 
-    pub fn change_front_audio(&mut self, path: String) {
-        self.get_audio_file(path.clone());
-        self.decks[self.current_deck].value.cards[self.current_card.unwrap()]
-            .front
-            .audio_path = Some(path);
+    /// Returns true once the answer was shown for at least `config.min_answer_time_ms`.
+    pub fn can_grade(&self, elapsed: Duration) -> bool {
+        elapsed >= Duration::from_millis(self.config.min_answer_time_ms)
     }
 
-    pub fn change_back_audio(&mut self, path: String) {
-        self.get_audio_file(path.clone());
+    /// Returns true if some cards of the current deck were answered wrong.
+    pub fn has_lapses(&self) -> bool {
+        self.lapses
+            .iter()
+            .any(|&(deck, _, _)| deck == self.current_deck)
+    }
+
+    /// Repeats cards of the current deck that were answered wrong, without rescheduling them.
+    pub fn start_lapse_review(&mut self) {
+        self.lapse_review = Some(
+            self.lapses
+                .iter()
+                .filter(|&&(deck, _, _)| deck == self.current_deck)
+                .map(|&(_, card_index, side)| (card_index, side))
+                .collect(),
+        );
+        self.current_card = None;
+    }
+
+    pub fn in_lapse_review(&self) -> bool {
+        self.lapse_review.is_some()
+    }
+
+    /// Writes changed decks to disk once `config.autosave_interval_secs` has passed.
+    pub fn autosave(&mut self) {
+        let interval = Duration::from_secs(self.config.autosave_interval_secs);
+        if interval.is_zero() || self.last_autosave.elapsed() < interval {
+            return;
+        }
+
+        for deck in self.decks.iter_mut().filter(|deck| deck.dirty) {
+            deck.save();
+        }
+        self.last_autosave = Instant::now();
+    }
+
+    /// Reloads decks changed by other programs while `config.watch_folder` is on.
+    pub fn process_folder_events(&mut self) {
+        if self.config.watch_folder != self.folder_watcher.is_some() {
+            self.folder_watcher = None;
+            if self.config.watch_folder {
+                match FolderWatcher::new(Path::new(&self.config.folder_path)) {
+                    Ok(watcher) => self.folder_watcher = Some(watcher),
+                    Err(error) => {
+                        log::error!("Can't watch the folder with decks: {}", error);
+                        self.config.watch_folder = false;
+                    }
+                }
+            }
+        }
+
+        let changed_decks = match &self.folder_watcher {
+            Some(watcher) => watcher.changed_decks(),
+            None => return,
+        };
+        for path in changed_decks {
+            self.reload_deck_file(&path);
+        }
+    }
+
+    /// Loads a deck file that was changed outside of the app.
+    /// Decks with changes that aren't saved yet are left as they are.
+    pub fn reload_deck_file(&mut self, path: &Path) {
+        let deck_index = self
+            .decks
+            .iter()
+            .position(|deck| Path::new(&deck.path).file_name() == path.file_name());
+
+        if let Some(deck_index) = deck_index {
+            let deck = &self.decks[deck_index];
+            if deck.dirty {
+                log::warn!("{} was changed on disk, keeping unsaved changes", deck.path);
+                return;
+            }
+            // Events for files the app just wrote itself
+            if fs::read_to_string(path).ok() == serde_json::to_string(&deck.value).ok() {
+                return;
+            }
+        }
+
+        let value = match data::load_deck(path) {
+            Ok(deck) => deck,
+            Err(error) => {
+                log::warn!("Can't reload {}: {}", path.display(), error);
+                return;
+            }
+        };
+
+        match deck_index {
+            Some(deck_index) => {
+                self.decks[deck_index].value = value;
+                self.review_undo
+                    .retain(|snapshot| snapshot.deck != deck_index);
+                if deck_index == self.current_deck
+                    && self
+                        .current_card
+                        .is_some_and(|card| card >= self.decks[deck_index].value.cards.len())
+                {
+                    self.current_card = None;
+                }
+            }
+            None => self.decks.push(DeckFromFile {
+                value,
+                path: path.to_string_lossy().to_string(),
+                dirty: false,
+            }),
+        }
+    }
+
+    /// Returns true when `config.break_after` cards were reviewed without a break.
+    pub fn should_break(&self) -> bool {
+        self.config.break_after != 0 && self.reviews_since_break >= self.config.break_after
+    }
+
+    pub fn take_break(&mut self) {
+        self.reviews_since_break = 0;
+    }
+
+    pub fn delete_card(&mut self) {
+        self.remove_card(self.current_deck, self.current_card.unwrap());
+        self.current_card = None;
+        self.save_current_deck();
+    }
+
+    /// Problems of the deck, see `Deck::health_report`.
+    pub fn health_report(&self, index: usize) -> HealthReport {
+        self.decks[index]
+            .value
+            .health_report(Path::new(&self.config.folder_path))
+    }
+
+    /// Stops cards of the deck from using media files that are missing.
+    /// Returns how many references were removed.
+    pub fn remove_broken_media(&mut self, index: usize) -> usize {
+        let broken_media = self.health_report(index).broken_media;
+        for broken in &broken_media {
+            let field = self.decks[index].value.cards[broken.card].field_mut(broken.side);
+            for path in [&mut field.audio_path, &mut field.image_path] {
+                if path.as_ref() == Some(&broken.file_name) {
+                    *path = None;
+                }
+            }
+        }
+        if !broken_media.is_empty() {
+            self.decks[index].dirty = true;
+            self.decks[index].save();
+        }
+        broken_media.len()
+    }
+
+    /// Deletes the cards of the deck that have no text, returns how many were deleted.
+    pub fn delete_empty_cards(&mut self, index: usize) -> usize {
+        let cards = self.health_report(index).empty_cards;
+        self.remove_cards(index, cards)
+    }
+
+    /// Deletes later copies of cards with the same front, returns how many were deleted.
+    pub fn delete_duplicate_copies(&mut self, index: usize) -> usize {
+        let cards = self
+            .health_report(index)
+            .duplicates
+            .into_iter()
+            .map(|(_, duplicate)| duplicate)
+            .collect();
+        self.remove_cards(index, cards)
+    }
+
+    fn remove_cards(&mut self, deck_index: usize, mut cards: Vec<usize>) -> usize {
+        // Later cards go first, so the other indices stay valid
+        cards.sort_unstable_by(|a, b| b.cmp(a));
+        cards.dedup();
+        for &card_index in &cards {
+            if deck_index == self.current_deck && self.current_card == Some(card_index) {
+                self.current_card = None;
+            }
+            self.remove_card(deck_index, card_index);
+        }
+        if !cards.is_empty() {
+            self.decks[deck_index].save();
+        }
+        cards.len()
+    }
+
+    /// Cards of the current deck with the same front, see `Deck::find_duplicates`.
+    pub fn find_duplicates(&self) -> Vec<(usize, usize)> {
+        match self.current_deck() {
+            Some(deck) => deck.value.find_duplicates(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Deletes a card of the current deck that duplicates another one.
+    pub fn remove_duplicate(&mut self, card_index: usize) {
+        if self.current_card == Some(card_index) {
+            self.current_card = None;
+        }
+        self.remove_card(self.current_deck, card_index);
+        self.save_current_deck();
+    }
+
+    /// Folds the duplicate into the kept card and deletes it. The kept card keeps its
+    /// scheduling, gets the duplicate's tags, and its back if that's different.
+    pub fn merge_duplicate(&mut self, kept: usize, duplicate: usize) {
+        let cards = &mut self.decks[self.current_deck].value.cards;
+        let duplicate_card = cards[duplicate].clone();
+        let kept_card = &mut cards[kept];
+        for tag in duplicate_card.tags {
+            if !kept_card.has_tag(&tag) {
+                kept_card.tags.push(tag);
+            }
+        }
+        let back = duplicate_card.back.text.trim();
+        if !back.is_empty() && back != kept_card.back.text.trim() {
+            kept_card.back.text = format!("{}\n{}", kept_card.back.text, back);
+        }
+        if kept_card.back.audio_path.is_none() {
+            kept_card.back.audio_path = duplicate_card.back.audio_path;
+        }
+
+        self.remove_duplicate(duplicate);
+    }
+
+    /// Moves a card to the end of another deck, keeping its scheduling and audio.
+    /// If the card was the current one, it stays current in its new deck.
+    pub fn move_card(&mut self, from_deck: usize, card_index: usize, to_deck: usize) {
+        if from_deck == to_deck {
+            return;
+        }
+
+        let card = self.decks[from_deck].value.cards[card_index].clone();
+        let was_current = from_deck == self.current_deck && self.current_card == Some(card_index);
+        self.remove_card(from_deck, card_index);
+        let new_index = self.decks[to_deck].value.add_card(card);
+        self.decks[to_deck].dirty = true;
+        self.decks[from_deck].save();
+        self.decks[to_deck].save();
+
+        if was_current {
+            self.set_current_deck(to_deck);
+            self.current_card = Some(new_index);
+            self.lapse_review = None;
+        }
+    }
+
+    /// Writes only the current deck, so a crash doesn't lose the last change.
+    fn save_current_deck(&mut self) {
+        self.decks[self.current_deck].save();
+    }
+
+    pub fn added_batch_len(&self) -> usize {
+        self.added_batch.len()
+    }
+
+    /// Removes all cards added since a deck was last opened for revision.
+    pub fn undo_last_add_batch(&mut self) {
+        let mut batch = std::mem::take(&mut self.added_batch);
+        // Removing from the end keeps indices of the remaining batch cards valid
+        batch.sort_unstable_by(|a, b| b.cmp(a));
+        for (deck, card_index) in batch {
+            if deck == self.current_deck && self.current_card == Some(card_index) {
+                self.current_card = None;
+            }
+            self.remove_card(deck, card_index);
+        }
+    }
+
+    /// Removes a card and moves back indices that pointed past it.
+    fn remove_card(&mut self, deck_index: usize, card_index: usize) {
+        self.decks[deck_index].value.remove_card(card_index);
+        self.decks[deck_index].dirty = true;
+
+        self.lapses
+            .retain(|&(deck, index, _)| (deck, index) != (deck_index, card_index));
+        self.added_batch
+            .retain(|&card| card != (deck_index, card_index));
+        let lapse_cards = self.lapses.iter_mut().map(|(deck, index, _)| (deck, index));
+        let added_cards = self
+            .added_batch
+            .iter_mut()
+            .map(|(deck, index)| (deck, index));
+        for (deck, index) in lapse_cards.chain(added_cards) {
+            if *deck == deck_index && *index > card_index {
+                *index -= 1;
+            }
+        }
+        self.review_undo
+            .retain(|snapshot| snapshot.deck != deck_index || snapshot.card_index != card_index);
+        for snapshot in &mut self.review_undo {
+            if snapshot.deck == deck_index && snapshot.card_index > card_index {
+                snapshot.card_index -= 1;
+            }
+        }
+
+        if deck_index != self.current_deck {
+            return;
+        }
+        if let Some(queue) = &mut self.lapse_review {
+            queue.retain(|&(index, _)| index != card_index);
+            for (index, _) in queue.iter_mut().filter(|(index, _)| *index > card_index) {
+                *index -= 1;
+            }
+        }
+        if let Some(current_card) = &mut self.current_card {
+            if *current_card > card_index {
+                *current_card -= 1;
+            }
+        }
+    }
+
+    /// Plays the clips one after another, stopping whatever was playing before.
+    /// Clips that can't be played are skipped, the error tells about the first one.
+    fn play_audio(&mut self, paths: Vec<String>) -> std::result::Result<(), String> {
+        let Some((_, stream_handle)) = &self.audio_output else {
+            return Err("No audio output is available".to_string());
+        };
+
+        // Dropping the old sink stops its clips
+        let sink =
+            Sink::try_new(stream_handle).map_err(|error| format!("Can't play audio: {}", error))?;
+        sink.set_volume(self.config.volume.clamp(0.0, 1.0));
+        let mut result = Ok(());
+        for path in paths {
+            let path = Path::new(&self.config.folder_path).join("audio").join(path);
+            let source = File::open(&path)
+                .map_err(|error| error.to_string())
+                .and_then(|file| {
+                    Decoder::new(BufReader::new(file)).map_err(|error| error.to_string())
+                });
+            match source {
+                Ok(source) => sink.append(source),
+                Err(error) => {
+                    log::error!("Can't play {}: {}", path.display(), error);
+                    if result.is_ok() {
+                        result = Err(format!("Can't play {}: {}", path.display(), error));
+                    }
+                }
+            }
+        }
+        self.audio_sink = Some(sink);
+        result
+    }
+
+    /// Applies `config.volume` to the audio that is playing.
+    pub fn update_volume(&self) {
+        if let Some(sink) = &self.audio_sink {
+            sink.set_volume(self.config.volume.clamp(0.0, 1.0));
+        }
+    }
+
+    pub fn stop_audio(&self) {
+        if let Some(sink) = &self.audio_sink {
+            sink.stop();
+        }
+    }
+
+    pub fn play_question_audio(&mut self) -> std::result::Result<(), String> {
+        let audio_path = self
+            .current_card()
+            .and_then(|card| card.question_field(self.current_side).audio_path.clone());
+        match audio_path {
+            Some(audio_path) => self.play_audio(vec![audio_path]),
+            None => Ok(()),
+        }
+    }
+
+    pub fn play_answer_audio(&mut self) -> std::result::Result<(), String> {
+        let audio_path = self
+            .current_card()
+            .and_then(|card| card.answer_field(self.current_side).audio_path.clone());
+        match audio_path {
+            Some(audio_path) => self.play_audio(vec![audio_path]),
+            None => Ok(()),
+        }
+    }
+
+    /// Plays the answer audio when the answer is shown, see `revealed_audio`.
+    pub fn play_revealed_audio(&mut self) -> std::result::Result<(), String> {
+        let paths = self.revealed_audio();
+        if paths.is_empty() {
+            return Ok(());
+        }
+        self.play_audio(paths)
+    }
+
+    /// Audio files queued when the answer is shown: the answer audio,
+    /// preceded by the question audio if `config.replay_front_on_answer` is on.
+    fn revealed_audio(&self) -> Vec<String> {
+        let Some(card) = self.current_card() else {
+            return Vec::new();
+        };
+        let mut paths = Vec::new();
+        if self.config.replay_front_on_answer {
+            paths.extend(card.question_field(self.current_side).audio_path.clone());
+        }
+        paths.extend(card.answer_field(self.current_side).audio_path.clone());
+        paths
+    }
+
+    /// Copies the file into the audio folder and returns the name it got there.
+    fn get_audio_file(&self, path: &Path) -> io::Result<String> {
+        self.get_media_file(path, "audio")
+    }
+
+    /// Copies the file into the images folder and returns the name it got there.
+    fn get_image_file(&self, path: &Path) -> io::Result<String> {
+        self.get_media_file(path, "images")
+    }
+
+    /// Copies the file into a subfolder of the folder with decks and returns its new name.
+    /// Non-UTF8 file names are lossily converted, since decks store paths as strings.
+    fn get_media_file(&self, path: &Path, folder: &str) -> io::Result<String> {
+        // Getting a file name
+        let source_file_name = path.file_name().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a file", path.display()),
+            )
+        })?;
+        let old_file_name = source_file_name.to_string_lossy().to_string();
+        if source_file_name.to_str().is_none() {
+            log::warn!(
+                "File name {:?} is not valid UTF-8, storing it as {:?}",
+                source_file_name,
+                old_file_name
+            );
+        }
+
+        // Media folder in folder with decks
+        let media_folder = Path::new(&self.config.folder_path).join(folder);
+        fs::create_dir_all(&media_folder)?;
+
+        // Path to a audio folder and old filename
+        let mut new_file_path = media_folder.join(old_file_name.clone());
+
+        // Add a number to the name if it's taken: "name (1).ext", "name (2).ext"...
+        if new_file_path.exists() {
+            for i in 1.. {
+                new_file_path = media_folder.join(numbered_file_name(&old_file_name, i));
+                if !new_file_path.exists() {
+                    break;
+                }
+            }
+        }
+
+        // Copy a file to the local folder
+        fs::copy(path, &new_file_path)?;
+        Ok(new_file_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string())
+    }
+
+    pub fn change_front_audio(&mut self, path: &Path) -> io::Result<()> {
+        let file_name = self.get_audio_file(path)?;
+        self.decks[self.current_deck].value.cards[self.current_card.unwrap()]
+            .front
+            .audio_path = Some(file_name);
+        self.decks[self.current_deck].dirty = true;
+        self.save_current_deck();
+        Ok(())
+    }
+
+    pub fn change_back_audio(&mut self, path: &Path) -> io::Result<()> {
+        let file_name = self.get_audio_file(path)?;
         self.decks[self.current_deck].value.cards[self.current_card.unwrap()]
             .back
-            .audio_path = Some(path);
+            .audio_path = Some(file_name);
+        self.decks[self.current_deck].dirty = true;
+        self.save_current_deck();
+        Ok(())
     }
 
-    pub fn front_audio_exists(&self) -> bool {
+    /// Speaks the text typed for the field and uses the recording as its audio.
+    pub fn generate_tts(&mut self, side: CardSide, lang: &str) -> std::result::Result<(), String> {
+        let text = match side {
+            CardSide::Front => &self.card_front,
+            CardSide::Back => &self.card_back,
+        };
+        if text.trim().is_empty() {
+            return Err("The field has no text to speak.".to_string());
+        }
+
+        let temp_folder = std::env::temp_dir().join("smart-learner");
+        fs::create_dir_all(&temp_folder).map_err(|error| error.to_string())?;
+        let temp_file = temp_folder.join(tts_file_name(text));
+        let copied = tts::synthesize(text, lang, &temp_file).and_then(|_| {
+            self.get_audio_file(&temp_file)
+                .map_err(|error| error.to_string())
+        });
+        // Nothing is lost if the temporary file stays
+        let _ = fs::remove_file(&temp_file);
+        let file_name = copied?;
+        self.decks[self.current_deck].value.cards[self.current_card.unwrap()]
+            .field_mut(side)
+            .audio_path = Some(file_name);
+        self.decks[self.current_deck].dirty = true;
+        self.save_current_deck();
+        Ok(())
+    }
+
+    pub fn change_front_image(&mut self, path: &Path) -> io::Result<()> {
+        let file_name = self.get_image_file(path)?;
         self.decks[self.current_deck].value.cards[self.current_card.unwrap()]
             .front
-            .audio_path
-            .is_some()
+            .image_path = Some(file_name);
+        self.decks[self.current_deck].dirty = true;
+        self.save_current_deck();
+        Ok(())
     }
 
-    pub fn back_audio_exists(&self) -> bool {
+    pub fn change_back_image(&mut self, path: &Path) -> io::Result<()> {
+        let file_name = self.get_image_file(path)?;
         self.decks[self.current_deck].value.cards[self.current_card.unwrap()]
             .back
-            .audio_path
-            .is_some()
+            .image_path = Some(file_name);
+        self.decks[self.current_deck].dirty = true;
+        self.save_current_deck();
+        Ok(())
+    }
+
+    pub fn front_image_after_answer(&self) -> bool {
+        self.current_card()
+            .is_some_and(|card| card.front.image_after_answer)
+    }
+
+    /// Hides the front image until the answer is shown.
+    pub fn set_front_image_after_answer(&mut self, after_answer: bool) {
+        self.decks[self.current_deck].value.cards[self.current_card.unwrap()]
+            .front
+            .image_after_answer = after_answer;
+        self.decks[self.current_deck].dirty = true;
+        self.save_current_deck();
+    }
+
+    /// Full path of the image on the asked field, images that wait for the answer
+    /// are only returned once it's shown.
+    pub fn question_image(&self, answer_shown: bool) -> Option<PathBuf> {
+        let card = self.current_card()?;
+        let image_path = card
+            .question_field(self.current_side)
+            .visible_image(answer_shown)?;
+        Some(
+            Path::new(&self.config.folder_path)
+                .join("images")
+                .join(image_path),
+        )
+    }
+
+    /// Full path of the image on the revealed field.
+    pub fn answer_image(&self) -> Option<PathBuf> {
+        let card = self.current_card()?;
+        let image_path = card.answer_field(self.current_side).image_path.as_ref()?;
+        Some(
+            Path::new(&self.config.folder_path)
+                .join("images")
+                .join(image_path),
+        )
+    }
+
+    /// Creates a card for every audio file in the folder, with the audio on the front
+    /// and the file name on the back. Returns how many cards were added.
+    pub fn import_audio_folder(&mut self, folder: &Path, deck_index: usize) -> io::Result<usize> {
+        let mut files: Vec<PathBuf> = folder
+            .read_dir()?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| {
+                path.extension()
+                    .and_then(|extension| extension.to_str())
+                    .is_some_and(|extension| {
+                        AUDIO_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+                    })
+            })
+            .collect();
+        files.sort();
+
+        let mut cards = Vec::new();
+        for file in files {
+            let file_name = self.get_audio_file(&file)?;
+            cards.push(Card::new(
+                Field {
+                    text: String::new(),
+                    audio_path: Some(file_name),
+                    image_path: None,
+                    image_after_answer: false,
+                },
+                Field {
+                    text: file.file_stem().unwrap().to_string_lossy().to_string(),
+                    audio_path: None,
+                    image_path: None,
+                    image_after_answer: false,
+                },
+            ));
+        }
+
+        let deck = &mut self.decks[deck_index];
+        deck.dirty = true;
+        let cards_before = deck.value.cards.len();
+        let added = deck.value.import_cards(cards);
+        self.added_batch
+            .extend((cards_before..cards_before + added).map(|card| (deck_index, card)));
+        Ok(added)
+    }
+
+    /// Adds cards from a CSV file with front and back columns, returns how many were added.
+    pub fn import_csv(
+        &mut self,
+        path: &Path,
+        deck_index: usize,
+    ) -> std::result::Result<usize, Box<dyn Error>> {
+        let cards = import::cards_from_csv(path)?;
+
+        let deck = &mut self.decks[deck_index];
+        deck.dirty = true;
+        let cards_before = deck.value.cards.len();
+        let added = deck.value.import_cards(cards);
+        self.added_batch
+            .extend((cards_before..cards_before + added).map(|card| (deck_index, card)));
+        Ok(added)
+    }
+
+    /// Adds cards from tab separated front and back lines, returns how many were added.
+    pub fn import_tsv_string(&mut self, text: &str, deck_index: usize) -> usize {
+        let cards = import::cards_from_tsv(text);
+
+        let deck = &mut self.decks[deck_index];
+        deck.dirty = true;
+        let cards_before = deck.value.cards.len();
+        let added = deck.value.import_cards(cards);
+        self.added_batch
+            .extend((cards_before..cards_before + added).map(|card| (deck_index, card)));
+        added
+    }
+
+    /// Writes the card with its audio and images into a zip that can be shared.
+    pub fn export_card_bundle(
+        &self,
+        deck: usize,
+        card: usize,
+        path: &Path,
+    ) -> std::result::Result<(), Box<dyn Error>> {
+        export::card_bundle(
+            &self.decks[deck].value.cards[card],
+            Path::new(&self.config.folder_path),
+            path,
+        )
+    }
+
+    /// Adds the card from a bundle to the current deck and makes it the current card.
+    /// Its media is copied in, files with taken names get a number.
+    pub fn import_card_bundle(&mut self, path: &Path) -> std::result::Result<(), Box<dyn Error>> {
+        if self.decks.is_empty() {
+            return Err("There is no deck to add the card to".into());
+        }
+
+        let mut card = import::card_from_bundle(path)?;
+        for field in [&mut card.front, &mut card.back] {
+            if let Some(unpacked) = field.audio_path.take() {
+                field.audio_path = Some(self.get_audio_file(Path::new(&unpacked))?);
+                fs::remove_file(unpacked)?;
+            }
+            if let Some(unpacked) = field.image_path.take() {
+                field.image_path = Some(self.get_image_file(Path::new(&unpacked))?);
+                fs::remove_file(unpacked)?;
+            }
+        }
+
+        let new_card = self.decks[self.current_deck].value.add_card(card);
+        self.decks[self.current_deck].dirty = true;
+        self.save_current_deck();
+        self.change_card(new_card);
+        Ok(())
+    }
+
+    /// Creates a deck from an Anki package, returns how many cards it got.
+    pub fn import_anki(&mut self, path: &Path) -> std::result::Result<usize, Box<dyn Error>> {
+        let mut deck = import::anki::deck_from_apkg(path)?;
+        deck.name = sanitize_deck_name(&deck.name);
+        let deck_path = Path::new(&self.config.folder_path)
+            .join(&deck.name)
+            .to_str()
+            .unwrap()
+            .to_string()
+            + ".sdeck";
+        if Path::new(&deck_path).exists() {
+            return Err(format!("A deck named {} already exists", deck.name).into());
+        }
+
+        // Both cards of a reversed note use the same file
+        let mut copied: HashMap<String, String> = HashMap::new();
+        for card in &mut deck.cards {
+            for field in [&mut card.front, &mut card.back] {
+                if let Some(unpacked) = field.audio_path.take() {
+                    let file_name = match copied.get(&unpacked) {
+                        Some(file_name) => file_name.clone(),
+                        None => self.get_audio_file(Path::new(&unpacked))?,
+                    };
+                    copied.insert(unpacked, file_name.clone());
+                    field.audio_path = Some(file_name);
+                }
+            }
+        }
+        for unpacked in copied.keys() {
+            fs::remove_file(unpacked)?;
+        }
+
+        let cards = deck.cards.len();
+        self.decks.push(DeckFromFile {
+            value: deck,
+            path: deck_path,
+            dirty: true,
+        });
+        self.decks.last_mut().unwrap().save();
+        Ok(cards)
+    }
+
+    pub fn question_audio_exists(&self) -> bool {
+        self.current_card()
+            .is_some_and(|card| card.question_field(self.current_side).audio_path.is_some())
+    }
+
+    pub fn answer_audio_exists(&self) -> bool {
+        self.current_card()
+            .is_some_and(|card| card.answer_field(self.current_side).audio_path.is_some())
+    }
+}
+
+/// The current deck is remembered, so the app starts on it next time.
+impl Drop for App {
+    fn drop(&mut self) {
+        self.remember_current_deck();
+        self.config.lapses = self
+            .lapses
+            .iter()
+            .map(|&(deck, card, side)| SavedLapse {
+                deck_name: self.decks[deck].value.name.clone(),
+                card,
+                side,
+                date: Date::current(),
+            })
+            .collect();
+    }
+}
+
+/// Deck names are file names, so they can't point to other folders.
+fn check_deck_name(name: &str) -> std::result::Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("The deck name can't be empty".to_string());
+    }
+    if name.contains(['/', '\\']) || name.contains("..") {
+        return Err(format!(
+            "{} can't be used as a deck name, it contains /, \\ or ..",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// Adds a number to the stem of the file name: "name (1).ext".
+/// Only the last extension is kept apart, so "a.b.mp3" becomes "a.b (1).mp3".
+fn numbered_file_name(file_name: &str, number: usize) -> String {
+    let path = Path::new(file_name);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    match path.extension() {
+        Some(extension) => format!("{} ({}).{}", stem, number, extension.to_string_lossy()),
+        None => format!("{} ({})", stem, number),
+    }
+}
+
+/// Names a recording after the text it speaks, so the media folder stays readable.
+/// Text without any usable characters is named by its hash.
+fn tts_file_name(text: &str) -> String {
+    let name: String = text
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("_")
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == '_' || *c == '-')
+        .take(40)
+        .collect();
+    if name.trim_matches('_').is_empty() {
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        return format!("tts_{:x}.wav", hasher.finish());
+    }
+    format!("{}.wav", name)
+}
+
+/// Turns a name from another program into one `check_deck_name` accepts.
+/// Anki separates subdecks with `::`, they become ` - `.
+fn sanitize_deck_name(name: &str) -> String {
+    let mut name = name.replace("::", " - ").replace(['/', '\\'], "-");
+    while name.contains("..") {
+        name = name.replace("..", ".");
+    }
+    if name.trim().is_empty() {
+        return "Imported deck".to_string();
+    }
+    name
+}
+
+/// Cuts text longer than `length` characters and ends it with an ellipsis.
+fn truncate(text: &str, length: usize) -> String {
+    match text.char_indices().nth(length) {
+        Some((end, _)) => format!("{}…", text[..end].trim_end()),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smart_learner_core::{answer::Strictness, deck::ReviewOrder};
+
+    /// App with an empty folder with decks in the temporary folder, named after the test.
+    fn test_app(name: &str) -> App {
+        let folder = std::env::temp_dir().join(format!("smart-learner-test-{}", name));
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        App::with_config(Config {
+            folder_path: folder.to_str().unwrap().to_string(),
+            ..Config::default()
+        })
+    }
+
+    #[test]
+    fn reload_keeps_decks_whose_files_broke() {
+        let mut app = test_app("reload-broken");
+        app.new_deck("Spanish".to_string());
+        fs::write(&app.decks[0].path, "not a deck").unwrap();
+
+        app.reload();
+        assert_eq!(app.decks.len(), 1);
+        assert_eq!(app.decks[0].value.name, "Spanish");
+        assert!(app.deck_errors().is_some());
+    }
+
+    /// Adds a deck with a card for every front, the cards get the tags.
+    fn add_deck(app: &mut App, name: &str, cards: &[(&str, &[&str])]) {
+        app.new_deck(name.to_string());
+        let deck = &mut app.decks.last_mut().unwrap().value;
+        for (front, tags) in cards {
+            let field = Field {
+                text: front.to_string(),
+                audio_path: None,
+                image_path: None,
+                image_after_answer: false,
+            };
+            let mut card = Card::new(field.clone(), field);
+            card.tags = tags.iter().map(|tag| tag.to_string()).collect();
+            deck.cards.push(card);
+        }
+    }
+
+    #[test]
+    fn tag_session_grades_cards_in_their_own_decks() {
+        let mut app = test_app("tag-session");
+        app.config.learning_steps = Vec::new();
+        add_deck(&mut app, "Biology", &[("cell", &["exam"]), ("leaf", &[])]);
+        add_deck(&mut app, "Chemistry", &[("atom", &["exam"])]);
+        app.open_deck(1);
+        app.start_tag_session("exam".to_string());
+
+        let mut revised = Vec::new();
+        while app.get_card_for_revision().0 {
+            revised.push((app.current_deck, app.get_question()));
+            app.card_revised(Result::Easy);
+        }
+
+        assert_eq!(
+            revised,
+            vec![(0, "cell".to_string()), (1, "atom".to_string())]
+        );
+        assert_eq!(app.decks[0].value.cards[0].history.len(), 1);
+        assert!(app.decks[0].value.cards[1].history.is_empty());
+        assert_eq!(app.decks[1].value.cards[0].history.len(), 1);
+    }
+
+    #[test]
+    fn global_new_budget_is_shared_by_decks() {
+        let mut app = test_app("global-new-budget");
+        app.config.learning_steps = Vec::new();
+        app.config.global_new_budget = Some(10);
+        let cards: Vec<(&str, &[&str])> = vec![("new", &[]); 8];
+        add_deck(&mut app, "Biology", &cards);
+        add_deck(&mut app, "Chemistry", &cards);
+
+        let mut served = 0;
+        for deck in 0..2 {
+            app.open_deck(deck);
+            while app.get_card_for_revision().0 {
+                app.card_revised(Result::Easy);
+                served += 1;
+            }
+        }
+        assert_eq!(served, 10);
+    }
+
+    #[test]
+    fn health_fixes_remove_problems() {
+        let mut app = test_app("health-fixes");
+        add_deck(
+            &mut app,
+            "Words",
+            &[("cat", &[]), ("", &[]), ("Cat", &[]), ("dog", &[])],
+        );
+        app.decks[0].value.cards[0].back.text = String::new();
+        app.decks[0].value.cards[3].front.image_path = Some("dog.png".to_string());
+
+        assert_eq!(app.remove_broken_media(0), 1);
+        assert!(app.decks[0].value.cards[3].front.image_path.is_none());
+        assert_eq!(app.delete_empty_cards(0), 1);
+        assert_eq!(app.delete_duplicate_copies(0), 1);
+        assert!(app.health_report(0).is_healthy());
+        let fronts: Vec<&str> = app.decks[0]
+            .value
+            .cards
+            .iter()
+            .map(|card| card.front.text.as_str())
+            .collect();
+        assert_eq!(fronts, vec!["cat", "dog"]);
+    }
+
+    #[test]
+    fn lapse_review_serves_wrong_sides() {
+        let mut app = test_app("lapse-review");
+        app.config.learning_steps = Vec::new();
+        add_deck(
+            &mut app,
+            "Words",
+            &[("cat", &[]), ("dog", &[]), ("fox", &[])],
+        );
+        app.decks[0].value.cards[1].bidirectional = true;
+        app.open_deck(0);
+
+        // Cards answered wrong are due again tomorrow, so every side is served once
+        while app.get_card_for_revision().0 {
+            let wrong = (app.current_card, app.current_side) == (Some(0), Side::Forward)
+                || (app.current_card, app.current_side) == (Some(1), Side::Reverse);
+            app.card_revised(if wrong { Result::Wrong } else { Result::Easy });
+        }
+
+        app.start_lapse_review();
+        let mut repeated = Vec::new();
+        while app.get_card_for_revision().0 {
+            repeated.push((app.current_card.unwrap(), app.current_side));
+            app.card_revised(Result::Easy);
+        }
+        assert!(repeated == vec![(0, Side::Forward), (1, Side::Reverse)]);
+    }
+
+    #[test]
+    fn new_session_replaces_lapses() {
+        let mut app = test_app("lapse-session");
+        app.config.learning_steps = Vec::new();
+        add_deck(&mut app, "Words", &[("cat", &[]), ("dog", &[])]);
+        app.open_deck(0);
+        app.get_card_for_revision();
+        app.card_revised(Result::Wrong);
+        assert!(app.has_lapses());
+
+        app.open_deck(0);
+        assert!(app.has_lapses());
+        app.get_card_for_revision();
+        app.card_revised(Result::Easy);
+        assert!(!app.has_lapses());
+    }
+
+    #[test]
+    fn todays_saved_lapses_are_restored() {
+        let mut app = test_app("lapse-restore");
+        add_deck(&mut app, "Words", &[("cat", &[]), ("dog", &[])]);
+        let lapse = |card, date| SavedLapse {
+            deck_name: "Words".to_string(),
+            card,
+            side: Side::Forward,
+            date,
+        };
+        app.config.lapses = vec![
+            lapse(1, Date::current()),
+            lapse(0, Date::current().sub_days(1)),
+            lapse(5, Date::current()),
+        ];
+
+        app.restore_lapses();
+        assert!(app.lapses == vec![(0, 1, Side::Forward)]);
+    }
+
+    #[test]
+    fn last_deck_name_follows_deck_changes() {
+        let mut app = test_app("last-deck-name");
+        app.new_deck("Spanish".to_string());
+        assert_eq!(app.config.last_deck_name.as_deref(), Some("Spanish"));
+        app.new_deck("German".to_string());
+        app.open_deck(1);
+        assert_eq!(app.config.last_deck_name.as_deref(), Some("German"));
+
+        app.rename_deck(1, "Deutsch".to_string()).unwrap();
+        assert_eq!(app.config.last_deck_name.as_deref(), Some("Deutsch"));
+        app.delete_deck(1).unwrap();
+        assert_eq!(app.config.last_deck_name.as_deref(), Some("Spanish"));
+        app.delete_deck(0).unwrap();
+        assert_eq!(app.config.last_deck_name, None);
+    }
+
+    #[test]
+    fn rename_rejects_names_outside_the_folder() {
+        let mut app = test_app("rename-invalid");
+        app.new_deck("Spanish".to_string());
+        for name in ["", "  ", "../Spanish", "a/b", "a\\b", ".."] {
+            assert!(app.rename_deck(0, name.to_string()).is_err());
+        }
+        assert_eq!(app.decks[0].value.name, "Spanish");
+        assert!(app.rename_deck(0, "Español".to_string()).is_ok());
+    }
+
+    #[test]
+    fn tts_files_are_named_after_text() {
+        assert_eq!(tts_file_name("¿Dónde está?"), "Dónde_está.wav");
+        assert_eq!(tts_file_name("  a/b  c "), "ab_c.wav");
+        assert_ne!(tts_file_name("hola"), tts_file_name("adiós"));
+
+        let hashed = tts_file_name("?!");
+        assert!(hashed.starts_with("tts_") && hashed.ends_with(".wav"));
+        assert_eq!(hashed, tts_file_name("?!"));
+        assert_ne!(hashed, tts_file_name("..."));
+    }
+
+    #[test]
+    fn imported_deck_names_are_sanitized() {
+        assert_eq!(sanitize_deck_name("Lang::Spanish"), "Lang - Spanish");
+        assert_eq!(sanitize_deck_name("a/b\\c"), "a-b-c");
+        assert_eq!(sanitize_deck_name("..."), ".");
+        assert_eq!(sanitize_deck_name(""), "Imported deck");
+        for name in ["Lang::Spanish", "../../etc", "a\\..\\b", " "] {
+            assert!(check_deck_name(&sanitize_deck_name(name)).is_ok());
+        }
+    }
+
+    #[test]
+    fn opening_a_deck_ends_tag_session() {
+        let mut app = test_app("tag-session-end");
+        add_deck(&mut app, "Biology", &[("cell", &["exam"])]);
+        app.start_tag_session("exam".to_string());
+        assert_eq!(app.tag_session(), Some("exam"));
+        app.open_deck(0);
+        assert_eq!(app.tag_session(), None);
+    }
+
+    #[test]
+    fn front_image_can_wait_for_the_answer() {
+        let mut app = test_app("image-after-answer");
+        app.new_deck("Pictures".to_string());
+        app.open_deck(0);
+        app.create_card();
+        app.decks[0].value.cards[0].front.image_path = Some("cat.png".to_string());
+
+        assert!(app.question_image(false).is_some());
+        app.set_front_image_after_answer(true);
+        assert!(app.front_image_after_answer());
+        assert!(app.question_image(false).is_none());
+        assert!(app.question_image(true).is_some());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_media_file_name_is_stored_lossily() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let app = test_app("non-utf8-media");
+        let source_folder = Path::new(&app.config.folder_path).join("source");
+        fs::create_dir_all(&source_folder).unwrap();
+        let source = source_folder.join(OsStr::from_bytes(b"caf\xe9.mp3"));
+        fs::write(&source, b"audio").unwrap();
+
+        let file_name = app.get_audio_file(&source).unwrap();
+        assert_eq!(file_name, "caf\u{FFFD}.mp3");
+        assert!(Path::new(&app.config.folder_path)
+            .join("audio")
+            .join(&file_name)
+            .exists());
+    }
+
+    #[test]
+    fn missing_media_file_is_an_error() {
+        let app = test_app("missing-media");
+        let source = Path::new(&app.config.folder_path).join("missing.mp3");
+        assert!(app.get_audio_file(&source).is_err());
+    }
+
+    #[test]
+    fn recent_decks_put_last_opened_first_without_duplicates() {
+        let mut app = test_app("recent-decks");
+        for name in ["Biology", "Chemistry", "Physics"] {
+            add_deck(&mut app, name, &[]);
+        }
+
+        app.open_deck(0);
+        app.open_deck(2);
+        app.open_deck(1);
+        app.open_deck(0);
+        assert_eq!(app.recent_decks(), vec![0, 1, 2]);
+        assert_eq!(app.config.recent_decks.len(), 3);
+    }
+
+    #[test]
+    fn break_is_suggested_after_configured_reviews() {
+        let mut app = test_app("break-after");
+        app.config.learning_steps = Vec::new();
+        app.config.break_after = 2;
+        add_deck(
+            &mut app,
+            "Biology",
+            &[("cell", &[]), ("leaf", &[]), ("root", &[])],
+        );
+        app.open_deck(0);
+
+        let mut suggested = Vec::new();
+        for _ in 0..2 {
+            app.get_card_for_revision();
+            app.card_revised(Result::Easy);
+            suggested.push(app.should_break());
+        }
+        assert_eq!(suggested, vec![false, true]);
+
+        app.take_break();
+        assert!(!app.should_break());
+        app.get_card_for_revision();
+        app.card_revised(Result::Easy);
+        assert!(!app.should_break());
+    }
+
+    #[test]
+    fn similar_card_starts_with_source_fields() {
+        let mut app = test_app("similar-card");
+        app.config.learning_steps = Vec::new();
+        add_deck(&mut app, "Biology", &[("cell", &["exam"])]);
+        app.decks[0].value.cards[0].back.text = "unit of life".to_string();
+        app.open_deck(0);
+        app.get_card_for_revision();
+        app.card_revised(Result::Easy);
+
+        app.create_similar_card(0);
+        assert_eq!(app.current_card_index(), Some(1));
+        assert_eq!(app.card_front, "cell");
+        assert_eq!(app.card_back, "unit of life");
+        assert_eq!(app.card_tags, "exam");
+        let card = &app.decks[0].value.cards[1];
+        assert!(card.is_new());
+        assert_eq!(card.schedule.current_repeat_in, 0);
+    }
+
+    #[test]
+    fn deck_status_tells_capped_decks_from_empty_ones() {
+        let mut app = test_app("deck-status");
+        app.config.learning_steps = Vec::new();
+        app.config.new_per_day = 1;
+        add_deck(&mut app, "Empty", &[]);
+        add_deck(&mut app, "Biology", &[("cell", &[]), ("leaf", &[])]);
+        add_deck(&mut app, "Chemistry", &[("atom", &[])]);
+        assert!(matches!(app.deck_status(0), DeckStatus::NoCards));
+        assert!(matches!(app.deck_status(1), DeckStatus::HasDue));
+
+        app.open_deck(1);
+        app.get_card_for_revision();
+        app.card_revised(Result::Easy);
+        assert!(matches!(app.deck_status(1), DeckStatus::LimitReached));
+
+        app.config.new_per_day = 0;
+        app.open_deck(2);
+        app.get_card_for_revision();
+        app.card_revised(Result::Easy);
+        match app.deck_status(2) {
+            DeckStatus::AllDoneToday { next_due } => {
+                assert!(next_due == Some(Date::current().add_days(1)))
+            }
+            _ => panic!("Chemistry should be done for today"),
+        }
+    }
+
+    #[test]
+    fn autosave_writes_only_dirty_decks_after_interval() {
+        let mut app = test_app("autosave");
+        app.config.autosave_interval_secs = 60;
+        add_deck(&mut app, "Biology", &[]);
+        add_deck(&mut app, "Chemistry", &[]);
+        app.decks[0].dirty = true;
+        fs::remove_file(&app.decks[0].path).unwrap();
+        fs::remove_file(&app.decks[1].path).unwrap();
+
+        app.autosave();
+        assert!(app.decks[0].dirty);
+
+        app.last_autosave = Instant::now() - Duration::from_secs(61);
+        app.autosave();
+        assert!(!app.decks[0].dirty);
+        assert!(Path::new(&app.decks[0].path).exists());
+        assert!(!Path::new(&app.decks[1].path).exists());
+    }
+
+    #[test]
+    fn deleting_cards_keeps_order_of_the_rest() {
+        let mut app = test_app("delete-order");
+        let cards: Vec<(&str, &[&str])> = vec![
+            ("one", &[]),
+            ("two", &[]),
+            ("three", &[]),
+            ("four", &[]),
+            ("five", &[]),
+        ];
+        add_deck(&mut app, "Numbers", &cards);
+        app.open_deck(0);
+
+        for card_index in [1, 2, 0] {
+            app.change_card(card_index);
+            app.delete_card();
+        }
+        let fronts: Vec<&str> = app.decks[0]
+            .value
+            .cards
+            .iter()
+            .map(|card| card.front.text.as_str())
+            .collect();
+        assert_eq!(fronts, vec!["three", "five"]);
+    }
+
+    #[test]
+    fn overlay_info_describes_current_card_and_deck() {
+        let mut app = test_app("overlay-info");
+        app.config.learning_steps = Vec::new();
+        add_deck(
+            &mut app,
+            "Biology",
+            &[("cell", &[]), ("leaf", &[]), ("root", &[])],
+        );
+        app.open_deck(0);
+        assert!(app.revision_overlay_info().is_none());
+
+        while app.get_card_for_revision().0 {
+            app.card_revised(Result::Easy);
+        }
+        app.change_card(0);
+        let info = app.revision_overlay_info().unwrap();
+        assert_eq!(info.interval, 1);
+        assert_eq!(info.due_cards, 0);
+        assert_eq!(info.total_cards, 3);
+
+        app.decks[0].value.cards[1].schedule.reschedule(0);
+        let info = app.revision_overlay_info().unwrap();
+        assert_eq!(info.due_cards, 1);
+    }
+
+    #[test]
+    fn audio_folder_gives_a_card_per_audio_file() {
+        let mut app = test_app("audio-folder");
+        add_deck(&mut app, "Spanish", &[]);
+        let source = std::env::temp_dir().join("smart-learner-test-audio-folder-source");
+        let _ = fs::remove_dir_all(&source);
+        fs::create_dir_all(&source).unwrap();
+        for file in ["hola.mp3", "adiós.wav", "gracias.ogg", "notes.txt"] {
+            fs::write(source.join(file), file).unwrap();
+        }
+
+        assert_eq!(app.import_audio_folder(&source, 0).unwrap(), 3);
+        let audio_folder = Path::new(&app.config.folder_path).join("audio");
+        let cards = &app.decks[0].value.cards;
+        let backs: Vec<&str> = cards.iter().map(|card| card.back.text.as_str()).collect();
+        assert_eq!(backs, vec!["adiós", "gracias", "hola"]);
+        for card in cards {
+            let audio = card.front.audio_path.as_ref().unwrap();
+            assert!(audio_folder.join(audio).exists());
+            assert!(card.front.text.is_empty());
+        }
+    }
+
+    /// Writes a deck with the fronts to the deck folder, like another program would.
+    fn write_deck_file(app: &App, file_name: &str, fronts: &[&str]) -> PathBuf {
+        let mut deck = Deck::new(file_name.to_string());
+        for front in fronts {
+            let field = Field {
+                text: front.to_string(),
+                audio_path: None,
+                image_path: None,
+                image_after_answer: false,
+            };
+            deck.cards.push(Card::new(field.clone(), field));
+        }
+        let path = Path::new(&app.config.folder_path).join(file_name);
+        fs::write(&path, serde_json::to_string(&deck).unwrap()).unwrap();
+        path
+    }
+
+    #[test]
+    fn changed_deck_file_is_reloaded() {
+        let mut app = test_app("watcher-reload");
+        add_deck(&mut app, "Biology", &[("cell", &[])]);
+        let path = write_deck_file(&app, "Biology.sdeck", &["leaf", "root"]);
+
+        app.reload_deck_file(&path);
+        assert_eq!(app.decks.len(), 1);
+        assert_eq!(app.decks[0].value.cards.len(), 2);
+
+        let path = write_deck_file(&app, "Chemistry.sdeck", &["atom"]);
+        app.reload_deck_file(&path);
+        assert_eq!(app.decks.len(), 2);
+    }
+
+    #[test]
+    fn deck_with_unsaved_changes_is_not_reloaded() {
+        let mut app = test_app("watcher-dirty");
+        add_deck(&mut app, "Biology", &[("cell", &[])]);
+        app.decks[0].dirty = true;
+        let path = write_deck_file(&app, "Biology.sdeck", &["leaf", "root"]);
+
+        app.reload_deck_file(&path);
+        assert_eq!(app.decks[0].value.cards.len(), 1);
+        assert!(app.decks[0].dirty);
+    }
+
+    #[test]
+    fn undoing_add_batch_removes_only_new_cards() {
+        let mut app = test_app("undo-batch");
+        add_deck(&mut app, "Biology", &[("cell", &[]), ("leaf", &[])]);
+        app.open_deck(0);
+
+        for front in ["root", "stem", "seed"] {
+            assert!(app.create_card());
+            app.card_front = front.to_string();
+            assert!(app.edit_card());
+        }
+        assert_eq!(app.added_batch_len(), 3);
+
+        app.undo_last_add_batch();
+        let fronts: Vec<&str> = app.decks[0]
+            .value
+            .cards
+            .iter()
+            .map(|card| card.front.text.as_str())
+            .collect();
+        assert_eq!(fronts, vec!["cell", "leaf"]);
+        assert_eq!(app.added_batch_len(), 0);
+        assert_eq!(app.current_card_index(), None);
+    }
+
+    #[test]
+    fn text_direction_is_saved_with_the_deck() {
+        let mut app = test_app("text-direction");
+        add_deck(&mut app, "Arabic", &[]);
+        add_deck(&mut app, "German", &[]);
+        app.open_deck(0);
+        app.set_text_direction(TextDirection::Rtl);
+        assert!(app.text_direction() == TextDirection::Rtl);
+        assert!(!app.decks[0].dirty);
+
+        let saved = data::load_deck(Path::new(&app.decks[0].path)).unwrap();
+        assert!(saved.text_direction == TextDirection::Rtl);
+        let saved = data::load_deck(Path::new(&app.decks[1].path)).unwrap();
+        assert!(saved.text_direction == TextDirection::Ltr);
+    }
+
+    #[test]
+    fn revealing_answer_queues_front_audio_when_replay_is_on() {
+        let mut app = test_app("replay-front");
+        add_deck(&mut app, "Spanish", &[("hola", &[])]);
+        let card = &mut app.decks[0].value.cards[0];
+        card.front.audio_path = Some("hola.mp3".to_string());
+        card.back.audio_path = Some("hello.mp3".to_string());
+        app.open_deck(0);
+        app.change_card(0);
+
+        assert_eq!(app.revealed_audio(), vec!["hello.mp3".to_string()]);
+        app.config.replay_front_on_answer = true;
+        assert_eq!(
+            app.revealed_audio(),
+            vec!["hola.mp3".to_string(), "hello.mp3".to_string()]
+        );
+    }
+
+    #[test]
+    fn next_due_deck_cycles_past_decks_without_due_cards() {
+        let mut app = test_app("next-due-deck");
+        add_deck(&mut app, "Biology", &[("cell", &[])]);
+        add_deck(&mut app, "Empty", &[]);
+        add_deck(&mut app, "Chemistry", &[("atom", &[])]);
+        app.open_deck(0);
+        assert_eq!(app.next_due_deck(), Some(2));
+
+        app.get_card_for_revision();
+        assert!(app.current_card_index().is_some());
+        assert!(app.switch_to_next_due_deck());
+        assert_eq!(app.current_deck, 2);
+        assert_eq!(app.current_card_index(), None);
+        assert_eq!(app.next_due_deck(), Some(0));
+
+        app.decks[0].value.cards[0].suspended = true;
+        assert_eq!(app.next_due_deck(), Some(2));
+        app.decks[2].value.cards[0].suspended = true;
+        assert_eq!(app.next_due_deck(), None);
+        assert!(!app.switch_to_next_due_deck());
+    }
+
+    #[test]
+    fn grading_waits_for_minimum_answer_time() {
+        let mut app = test_app("can-grade");
+        app.config.min_answer_time_ms = 1500;
+        assert!(!app.can_grade(Duration::ZERO));
+        assert!(!app.can_grade(Duration::from_millis(1499)));
+        assert!(app.can_grade(Duration::from_millis(1500)));
+        assert!(app.can_grade(Duration::from_secs(10)));
+
+        app.config.min_answer_time_ms = 0;
+        assert!(app.can_grade(Duration::ZERO));
+    }
+
+    #[test]
+    fn audio_from_outside_folder_is_stored_by_file_name() {
+        let mut app = test_app("audio-file-name");
+        add_deck(&mut app, "Spanish", &[("hola", &[])]);
+        app.open_deck(0);
+        app.change_card(0);
+        let source_folder = std::env::temp_dir().join("smart-learner-test-audio-file-name-source");
+        fs::create_dir_all(&source_folder).unwrap();
+        let source = source_folder.join("hello.mp3");
+        fs::write(&source, b"audio").unwrap();
+
+        app.change_front_audio(&source).unwrap();
+        let audio_path = app.decks[0].value.cards[0].front.audio_path.clone();
+        assert_eq!(audio_path.as_deref(), Some("hello.mp3"));
+        assert!(Path::new(&app.config.folder_path)
+            .join("audio")
+            .join("hello.mp3")
+            .exists());
+    }
+
+    #[test]
+    fn numbered_file_names_keep_stem_and_extension() {
+        assert_eq!(numbered_file_name("hello.mp3", 1), "hello (1).mp3");
+        assert_eq!(numbered_file_name("a.b.mp3", 1), "a.b (1).mp3");
+        assert_eq!(numbered_file_name("my.word.mp3", 2), "my.word (2).mp3");
+        assert_eq!(numbered_file_name("recording", 1), "recording (1)");
+        assert_eq!(numbered_file_name(".hidden", 3), ".hidden (3)");
+    }
+
+    #[test]
+    fn copying_taken_names_adds_numbers() {
+        let app = test_app("taken-media-names");
+        let source_folder = Path::new(&app.config.folder_path).join("source");
+        fs::create_dir_all(&source_folder).unwrap();
+
+        for (file, copies) in [
+            ("a.b.mp3", ["a.b.mp3", "a.b (1).mp3", "a.b (2).mp3"]),
+            ("recording", ["recording", "recording (1)", "recording (2)"]),
+        ] {
+            let source = source_folder.join(file);
+            fs::write(&source, b"audio").unwrap();
+            for copy in copies {
+                assert_eq!(app.get_audio_file(&source).unwrap(), copy);
+            }
+        }
+    }
+
+    #[test]
+    fn media_extensions_are_matched_ignoring_case() {
+        let mut app = test_app("extension-case");
+        add_deck(&mut app, "Spanish", &[]);
+        let source = Path::new(&app.config.folder_path).join("source");
+        fs::create_dir_all(&source).unwrap();
+        for file in [
+            "HOLA.MP3",
+            "Gracias.Ogg",
+            "adiós.wav",
+            "Photo.Png",
+            "notes.TXT",
+        ] {
+            fs::write(source.join(file), file).unwrap();
+        }
+
+        assert_eq!(app.import_audio_folder(&source, 0).unwrap(), 3);
+        assert_eq!(
+            app.get_image_file(&source.join("Photo.Png")).unwrap(),
+            "Photo.Png"
+        );
+        assert_eq!(numbered_file_name("Photo.Png", 1), "Photo (1).Png");
+    }
+
+    #[test]
+    fn reviewing_a_card_buries_its_sibling_in_another_deck() {
+        let mut app = test_app("bury-siblings");
+        app.config.learning_steps = Vec::new();
+        app.config.bury_siblings = true;
+        app.config.review_order = ReviewOrder::Added;
+        add_deck(&mut app, "Spanish", &[("cat", &[]), ("dog", &[])]);
+        add_deck(&mut app, "Spanish reverse", &[("perro", &[])]);
+        app.open_deck(0);
+        app.create_similar_card(0);
+        app.move_card(0, 2, 1);
+        let note_id = app.decks[0].value.cards[0].note_id;
+        assert!(note_id.is_some());
+        assert_eq!(app.decks[1].value.cards[1].note_id, note_id);
+
+        app.open_deck(0);
+        app.get_card_for_revision();
+        assert_eq!(app.get_question(), "cat");
+        app.card_revised(Result::Easy);
+
+        app.open_deck(1);
+        let mut served = Vec::new();
+        while app.get_card_for_revision().0 {
+            served.push(app.get_question());
+            app.card_revised(Result::Easy);
+        }
+        assert_eq!(served, vec!["perro".to_string()]);
+
+        app.config.bury_siblings = false;
+        assert!(app.get_card_for_revision().0);
+        assert_eq!(app.get_question(), "cat");
+    }
+
+    #[test]
+    fn id_search_finds_exactly_one_card() {
+        let mut app = test_app("id-search");
+        add_deck(
+            &mut app,
+            "Biology",
+            &[("cell", &[]), ("leaf", &[]), ("root", &[])],
+        );
+        app.decks[0].value.assign_card_ids();
+        app.open_deck(0);
+
+        app.search_text = "id:2".to_string();
+        let results = app.search();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, 1);
+        assert_eq!(results[0].1, "leaf");
+
+        for search in ["id:4", "id:", "id:leaf"] {
+            app.search_text = search.to_string();
+            assert!(app.search().is_empty());
+        }
+    }
+
+    #[test]
+    fn typed_answers_are_compared_with_the_back() {
+        let mut app = test_app("typed-answer");
+        app.config.learning_steps = Vec::new();
+        add_deck(&mut app, "Spanish", &[("cat", &[]), ("dog", &[])]);
+        app.decks[0].value.cards[0].back.text = "Gato".to_string();
+        app.decks[0].value.cards[0].kind = CardKind::TypeAnswer;
+        app.config.review_order = ReviewOrder::Added;
+        app.open_deck(0);
+
+        app.get_card_for_revision();
+        assert!(app.asks_typed_answer());
+        app.typed_answer = "gato".to_string();
+        assert!(app.compare_typed_answer().unwrap().correct);
+        app.config.answer_strictness = Strictness::Exact;
+        assert!(!app.compare_typed_answer().unwrap().correct);
+
+        app.card_revised(Result::Easy);
+        app.get_card_for_revision();
+        assert!(!app.asks_typed_answer());
+        assert!(app.typed_answer.is_empty());
+        assert!(app.compare_typed_answer().is_none());
+    }
+
+    #[test]
+    fn undoing_add_batch_keeps_current_card_after_a_new_one() {
+        let mut app = test_app("undo-batch-current");
+        add_deck(&mut app, "Biology", &[("cell", &[])]);
+        add_deck(&mut app, "Botany", &[("leaf", &[])]);
+        app.open_deck(0);
+        assert!(app.create_card());
+        app.card_front = "root".to_string();
+        assert!(app.edit_card());
+
+        // The moved card comes right after the new one and isn't part of the batch
+        app.move_card(1, 0, 0);
+        app.change_card(2);
+        app.undo_last_add_batch();
+
+        assert_eq!(app.current_card_index(), Some(1));
+        let card = &app.decks[0].value.cards[1];
+        assert_eq!(card.front.text, "leaf");
+    }
+
+    #[test]
+    fn last_deck_name_follows_moved_current_card() {
+        let mut app = test_app("last-deck-move");
+        add_deck(&mut app, "Spanish", &[("hola", &[])]);
+        add_deck(&mut app, "German", &[]);
+        app.open_deck(0);
+        app.change_card(0);
+
+        app.move_card(0, 0, 1);
+        assert_eq!(app.current_deck_index(), 1);
+        assert_eq!(app.config.last_deck_name.as_deref(), Some("German"));
+        app.set_current_deck(0);
+        assert_eq!(app.config.last_deck_name.as_deref(), Some("Spanish"));
+    }
+
+    #[test]
+    fn undoing_review_after_merge_keeps_ids_unique() {
+        let mut app = test_app("undo-merge");
+        app.config.learning_steps = Vec::new();
+        add_deck(&mut app, "Spanish", &[("hola", &[])]);
+        add_deck(&mut app, "German", &[("hallo", &[])]);
+        for deck in &mut app.decks {
+            deck.value.assign_card_ids();
+        }
+        app.open_deck(0);
+        assert!(app.get_card_for_revision().0);
+        app.card_revised(Result::Easy);
+
+        app.merge_decks(0, 1).unwrap();
+        app.undo_last_review();
+
+        let cards = &app.decks[0].value.cards;
+        assert!(cards[1].history.is_empty());
+        assert_ne!(cards[0].id, cards[1].id);
+        assert_eq!(app.decks[0].value.card_by_id(cards[1].id).unwrap().0, 1);
+    }
+
+    #[test]
+    fn saving_clears_unsaved_changes() {
+        let mut app = test_app("save-dirty");
+        add_deck(&mut app, "Spanish", &[("hola", &[])]);
+        add_deck(&mut app, "German", &[]);
+        app.decks[0].dirty = true;
+        app.decks[1].dirty = true;
+
+        app.save().unwrap();
+        assert!(app.decks.iter().all(|deck| !deck.dirty));
     }
 }
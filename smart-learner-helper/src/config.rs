@@ -1,19 +1,231 @@
 use serde_derive::{Deserialize, Serialize};
+use smart_learner_core::{answer::Strictness, card::Side, date::Date, deck::ReviewOrder};
 use std::env;
 
 #[derive(Serialize, Deserialize)]
 pub struct Config {
     pub folder_path: String,
+    #[serde(default)]
+    pub recent_decks: Vec<RecentDeck>,
+    /// Deck that was current when the app was closed, it's selected again on start.
+    #[serde(default)]
+    pub last_deck_name: Option<String>,
+    /// Number of reviews in a row after which a break is suggested, 0 disables it.
+    #[serde(default = "default_break_after")]
+    pub break_after: u32,
+    /// Seconds without input after which the session time stops counting, 0 disables it.
+    #[serde(default = "default_idle_timeout_secs")]
+    pub idle_timeout_secs: u64,
+    /// How often changed decks are written to disk, 0 disables autosave.
+    #[serde(default = "default_autosave_interval_secs")]
+    pub autosave_interval_secs: u64,
+    /// Shortest interval in days a reviewed card can get.
+    #[serde(default = "default_min_interval_days")]
+    pub min_interval_days: u64,
+    #[serde(default = "default_hard_multiplier")]
+    pub hard_multiplier: f64,
+    /// Multiplies intervals after an easy answer, on top of the ease factor.
+    #[serde(default = "default_easy_multiplier", alias = "easy_bonus")]
+    pub easy_multiplier: f64,
+    /// Interval in days after a wrong answer, 0 repeats the card in the same session.
+    #[serde(default = "default_wrong_interval")]
+    pub wrong_interval: u64,
+    /// Minutes until a card answered wrong is shown again, used when there are learning steps.
+    #[serde(default = "default_again_interval_mins")]
+    pub again_interval_mins: u64,
+    /// Interval in days a new card gets when it graduates with a difficult answer.
+    #[serde(default = "default_graduating_interval")]
+    pub graduating_interval: u64,
+    /// Interval in days a new card gets when it graduates with an easy answer.
+    #[serde(default = "default_easy_interval")]
+    pub easy_interval: u64,
+    /// Minutes between reviews of new and forgotten cards before they get day intervals.
+    #[serde(default = "default_learning_steps")]
+    pub learning_steps: Vec<u64>,
+    /// How long grade buttons stay disabled after the answer is shown.
+    #[serde(default)]
+    pub min_answer_time_ms: u64,
+    /// How closely answers typed for type answer cards have to match.
+    #[serde(default)]
+    pub answer_strictness: Strictness,
+    /// Play audio on its own when a card is asked and when the answer is shown.
+    #[serde(default = "default_autoplay_audio")]
+    pub autoplay_audio: bool,
+    /// Playback volume from 0.0 to 1.0.
+    #[serde(default = "default_volume")]
+    pub volume: f32,
+    /// Play the front audio again before the back audio when the answer is shown.
+    #[serde(default)]
+    pub replay_front_on_answer: bool,
+    /// Show bold, italic and code text and lists written in Markdown during revision.
+    #[serde(default = "default_render_markdown")]
+    pub render_markdown: bool,
+    /// Reload decks that were changed by other programs.
+    #[serde(default)]
+    pub watch_folder: bool,
+    /// Wrong answers after which a card is tagged as a leech and suspended, 0 disables it.
+    #[serde(default = "default_leech_threshold")]
+    pub leech_threshold: u32,
+    /// Never reviewed cards served per deck and day, 0 means no limit.
+    #[serde(default)]
+    pub new_per_day: u32,
+    /// Reviews of other cards per deck and day, 0 means no limit.
+    #[serde(default)]
+    pub reviews_per_day: u32,
+    /// Never reviewed cards served per day in all decks together, None means no shared limit.
+    #[serde(default)]
+    pub global_new_budget: Option<usize>,
+    /// Don't serve new siblings of cards reviewed today, in any deck.
+    #[serde(default)]
+    pub bury_siblings: bool,
+    #[serde(default)]
+    pub review_order: ReviewOrder,
+    /// Characters of card text shown in browser results, longer text ends with an ellipsis.
+    #[serde(default = "default_preview_length")]
+    pub preview_length: usize,
+    #[serde(default)]
+    pub theme: Theme,
+    /// Voice used when audio is generated from card text, an espeak language code.
+    #[serde(default = "default_tts_language")]
+    pub tts_language: String,
+    /// Cards answered wrong in the last sessions, so they can be repeated after a restart.
+    #[serde(default)]
+    pub lapses: Vec<SavedLapse>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+pub enum Theme {
+    /// Follow the theme of the system.
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+fn default_break_after() -> u32 {
+    50
+}
+
+fn default_idle_timeout_secs() -> u64 {
+    60
+}
+
+fn default_autosave_interval_secs() -> u64 {
+    60
+}
+
+fn default_min_interval_days() -> u64 {
+    1
+}
+
+fn default_hard_multiplier() -> f64 {
+    1.2
+}
+
+fn default_easy_multiplier() -> f64 {
+    1.3
+}
+
+fn default_wrong_interval() -> u64 {
+    1
+}
+
+fn default_again_interval_mins() -> u64 {
+    1
+}
+
+fn default_graduating_interval() -> u64 {
+    1
+}
+
+fn default_easy_interval() -> u64 {
+    1
+}
+
+fn default_learning_steps() -> Vec<u64> {
+    vec![1, 10]
+}
+
+fn default_leech_threshold() -> u32 {
+    8
+}
+
+fn default_autoplay_audio() -> bool {
+    true
+}
+
+fn default_render_markdown() -> bool {
+    true
+}
+
+fn default_volume() -> f32 {
+    1.0
+}
+
+fn default_preview_length() -> usize {
+    80
+}
+
+fn default_tts_language() -> String {
+    "en".to_string()
+}
+
+/// A deck the user studied recently, most recent decks come first in `Config::recent_decks`.
+#[derive(Serialize, Deserialize)]
+pub struct RecentDeck {
+    pub name: String,
+    pub opened: Date,
+}
+
+/// A side of a card answered wrong, only lapses from today are restored.
+#[derive(Serialize, Deserialize)]
+pub struct SavedLapse {
+    pub deck_name: String,
+    pub card: usize,
+    pub side: Side,
+    pub date: Date,
 }
 
 impl ::std::default::Default for Config {
     fn default() -> Self {
         Self {
             folder_path: env::current_dir().unwrap().to_str().unwrap().to_string(),
+            recent_decks: Vec::new(),
+            last_deck_name: None,
+            break_after: default_break_after(),
+            idle_timeout_secs: default_idle_timeout_secs(),
+            autosave_interval_secs: default_autosave_interval_secs(),
+            min_interval_days: default_min_interval_days(),
+            hard_multiplier: default_hard_multiplier(),
+            easy_multiplier: default_easy_multiplier(),
+            wrong_interval: default_wrong_interval(),
+            again_interval_mins: default_again_interval_mins(),
+            graduating_interval: default_graduating_interval(),
+            easy_interval: default_easy_interval(),
+            learning_steps: default_learning_steps(),
+            min_answer_time_ms: 0,
+            answer_strictness: Strictness::default(),
+            autoplay_audio: default_autoplay_audio(),
+            volume: default_volume(),
+            replay_front_on_answer: false,
+            render_markdown: default_render_markdown(),
+            watch_folder: false,
+            leech_threshold: default_leech_threshold(),
+            new_per_day: 0,
+            reviews_per_day: 0,
+            global_new_budget: None,
+            bury_siblings: false,
+            review_order: ReviewOrder::default(),
+            preview_length: default_preview_length(),
+            theme: Theme::default(),
+            tts_language: default_tts_language(),
+            lapses: Vec::new(),
         }
     }
 }
 
+// Tests must not overwrite the user's config
+#[cfg(not(test))]
 impl Drop for Config {
     fn drop(&mut self) {
         confy::store("smart-learner", None, self).unwrap();
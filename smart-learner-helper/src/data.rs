@@ -1,7 +1,8 @@
 use std::{
-    fs::OpenOptions,
+    error::Error,
+    fs::{self, OpenOptions},
     io::{self, Write},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
 use smart_learner_core::deck::Deck;
@@ -9,39 +10,165 @@ use smart_learner_core::deck::Deck;
 pub struct DeckFromFile {
     pub value: Deck,
     pub path: String,
+    /// Deck has changes that aren't written to the file yet.
+    pub dirty: bool,
 }
 
+impl DeckFromFile {
+    pub fn save(&mut self) {
+        match save_deck(&self.value, Path::new(&self.path)) {
+            Ok(()) => self.dirty = false,
+            Err(error) => log::error!("Can't save {}: {}", self.path, error),
+        }
+    }
+}
+
+/// Unsaved changes are written when the deck is dropped.
 impl Drop for DeckFromFile {
     fn drop(&mut self) {
-        let data = serde_json::to_string(&self.value).unwrap();
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .open(&self.path)
-            .unwrap();
-        let _ = file.write(data.as_bytes());
+        if self.dirty {
+            self.save();
+        }
     }
 }
 
-pub fn fetch_decks(path: &Path) -> Vec<DeckFromFile> {
+/// Makes sure decks can be stored at the path,
+/// creating the folder and its audio folder if they don't exist.
+pub fn prepare_folder(path: &Path) -> Result<(), String> {
+    if path.exists() && !path.is_dir() {
+        return Err(format!(
+            "{} is a file, decks have to be stored in a folder",
+            path.display()
+        ));
+    }
+
+    let audio_path = path.join("audio");
+    fs::create_dir_all(&audio_path)
+        .map_err(|error| format!("Can't create {}: {}", audio_path.display(), error))
+}
+
+/// Loads every deck file in the folder. Files that can't be loaded are skipped
+/// and returned with the reason, so one broken deck doesn't hide the others.
+pub fn fetch_decks(path: &Path) -> (Vec<DeckFromFile>, Vec<(PathBuf, String)>) {
     let mut decks = Vec::new();
-    for file in path.read_dir().unwrap() {
-        let path = file.unwrap();
-        if path.file_name().to_str().unwrap().contains(".sdeck") {
-            let path = &path.path();
-            decks.push(DeckFromFile {
-                value: load_deck(path),
-                path: path.to_str().unwrap().to_string(),
-            });
+    let mut errors = Vec::new();
+    let entries = match path.read_dir() {
+        Ok(entries) => entries,
+        Err(error) => {
+            errors.push((path.to_path_buf(), error.to_string()));
+            return (decks, errors);
         }
+    };
+
+    for entry in entries {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(error) => {
+                errors.push((path.to_path_buf(), error.to_string()));
+                continue;
+            }
+        };
+        if path
+            .extension()
+            .map_or(true, |extension| extension != "sdeck")
+        {
+            continue;
+        }
+
+        // Decks keep their paths as strings
+        let Some(path_text) = path.to_str() else {
+            errors.push((path.clone(), "the file name isn't valid UTF-8".to_string()));
+            continue;
+        };
+        match load_deck(&path) {
+            Ok(value) => decks.push(DeckFromFile {
+                value,
+                path: path_text.to_string(),
+                dirty: false,
+            }),
+            Err(error) => errors.push((path, error.to_string())),
+        }
+    }
+
+    for (path, error) in &errors {
+        log::error!("Can't load {}: {}", path.display(), error);
+    }
+    (decks, errors)
+}
+
+/// Writes every deck to its file, creating the folder with decks if it doesn't exist.
+pub fn save_decks(decks: &mut [DeckFromFile], folder: &Path) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(folder)?;
+    for deck in decks {
+        save_deck(&deck.value, Path::new(&deck.path))?;
+        deck.dirty = false;
     }
-    decks
+    Ok(())
+}
+
+pub fn save_deck(deck: &Deck, path: &Path) -> Result<(), Box<dyn Error>> {
+    let data = serde_json::to_string(deck)?;
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?;
+    file.write_all(data.as_bytes())?;
+    Ok(())
 }
 
-fn load_deck(path: &Path) -> Deck {
-    let file = OpenOptions::new().read(true).open(path).unwrap();
-    let data = io::read_to_string(file).unwrap();
-    let mut deck: Deck = serde_json::from_str(&data).unwrap();
+pub fn load_deck(path: &Path) -> Result<Deck, Box<dyn Error>> {
+    let file = OpenOptions::new().read(true).open(path)?;
+    let data = io::read_to_string(file)?;
+    let mut deck: Deck = serde_json::from_str(&data)?;
     deck.update();
-    deck
+    deck.assign_card_ids();
+    Ok(deck)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn broken_deck_files_are_skipped_and_reported() {
+        let folder = std::env::temp_dir().join("smart-learner-test-broken-deck");
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("broken.sdeck"), "not a deck").unwrap();
+        fs::write(folder.join("notes.txt"), "not a deck either").unwrap();
+
+        let (decks, errors) = fetch_decks(&folder);
+        assert!(decks.is_empty());
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, folder.join("broken.sdeck"));
+    }
+
+    #[test]
+    fn missing_folder_is_reported() {
+        let folder = std::env::temp_dir().join("smart-learner-test-missing-folder");
+        let _ = fs::remove_dir_all(&folder);
+
+        let (decks, errors) = fetch_decks(&folder);
+        assert!(decks.is_empty());
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn file_is_rejected_as_deck_folder() {
+        let file = std::env::temp_dir().join("smart-learner-test-folder-is-file");
+        let _ = fs::remove_dir_all(&file);
+        fs::write(&file, "").unwrap();
+
+        assert!(prepare_folder(&file).is_err());
+    }
+
+    #[test]
+    fn prepared_folder_has_audio_folder() {
+        let folder = std::env::temp_dir().join("smart-learner-test-prepare-folder");
+        let _ = fs::remove_dir_all(&folder);
+
+        assert!(prepare_folder(&folder).is_ok());
+        assert!(folder.join("audio").is_dir());
+    }
 }
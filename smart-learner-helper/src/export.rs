@@ -0,0 +1,383 @@
+use std::{
+    collections::HashSet,
+    error::Error,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use printpdf::{BuiltinFont, Mm, PdfDocument};
+use smart_learner_core::{card::Card, deck::Deck, field::Field};
+use zip::{write::FileOptions, ZipWriter};
+
+// Sizes are in millimeters, A4 page.
+const PAGE_WIDTH: f64 = 210.0;
+const PAGE_HEIGHT: f64 = 297.0;
+const MARGIN: f64 = 15.0;
+const LINE_HEIGHT: f64 = 5.0;
+const FONT_SIZE: f64 = 11.0;
+const TITLE_FONT_SIZE: f64 = 16.0;
+/// Roughly how many characters of the font fit into one column.
+const COLUMN_WIDTH: usize = 42;
+
+/// Name of the card inside a card bundle, media goes into `audio/` and `images/`.
+pub const BUNDLE_CARD_FILE: &str = "card.json";
+/// Name of the deck inside a deck bundle, media is stored like in card bundles.
+pub const BUNDLE_DECK_FILE: &str = "deck.json";
+
+/// Text formats a deck can be exported to.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    /// Front, back and tags of every card.
+    Csv,
+    /// The whole deck including scheduling.
+    Json,
+    /// Zip with the cards and their media but without reviews and scheduling, for sharing.
+    CleanBundle,
+}
+
+/// `folder` is the folder with decks, bundles read media from it.
+pub fn export_deck(
+    deck: &Deck,
+    folder: &Path,
+    path: &Path,
+    format: ExportFormat,
+) -> Result<(), Box<dyn Error>> {
+    match format {
+        ExportFormat::Csv => deck_to_csv(deck, path),
+        ExportFormat::Json => deck_to_json(deck, path),
+        ExportFormat::CleanBundle => clean_deck_bundle(deck, folder, path),
+    }
+}
+
+fn deck_to_csv(deck: &Deck, path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(["front", "back", "tags"])?;
+    for card in &deck.cards {
+        writer.write_record([
+            card.front.text.as_str(),
+            card.back.text.as_str(),
+            card.tags.join(", ").as_str(),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn deck_to_json(deck: &Deck, path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut deck = deck.clone();
+    // Decks from older versions may have full paths to audio
+    for card in &mut deck.cards {
+        strip_audio_folder(&mut card.front);
+        strip_audio_folder(&mut card.back);
+    }
+
+    serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), &deck)?;
+    Ok(())
+}
+
+fn strip_audio_folder(field: &mut Field) {
+    if let Some(audio_path) = &field.audio_path {
+        if let Some(file_name) = Path::new(audio_path).file_name() {
+            field.audio_path = Some(file_name.to_string_lossy().to_string());
+        }
+    }
+}
+
+/// Writes a zip with the card and the audio and images it uses, so it can be shared.
+/// `folder` is the folder with decks, media is read from its `audio` and `images` folders.
+pub fn card_bundle(card: &Card, folder: &Path, path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut zip = ZipWriter::new(File::create(path)?);
+    let options = FileOptions::default();
+    zip.start_file(BUNDLE_CARD_FILE, options)?;
+    zip.write_all(serde_json::to_string_pretty(card)?.as_bytes())?;
+    write_bundle_media(&mut zip, std::slice::from_ref(card), folder)?;
+    zip.finish()?;
+    Ok(())
+}
+
+/// Copy of the deck with every card new, see `Card::reset_progress`.
+pub fn clean_deck(deck: &Deck) -> Deck {
+    let mut deck = deck.clone();
+    for card in &mut deck.cards {
+        card.reset_progress();
+    }
+    deck
+}
+
+/// Writes a zip with the deck stripped by `clean_deck` and the media its cards use.
+fn clean_deck_bundle(deck: &Deck, folder: &Path, path: &Path) -> Result<(), Box<dyn Error>> {
+    let deck = clean_deck(deck);
+    let mut zip = ZipWriter::new(File::create(path)?);
+    zip.start_file(BUNDLE_DECK_FILE, FileOptions::default())?;
+    zip.write_all(serde_json::to_string_pretty(&deck)?.as_bytes())?;
+    write_bundle_media(&mut zip, &deck.cards, folder)?;
+    zip.finish()?;
+    Ok(())
+}
+
+/// Adds the audio and images of the cards to the zip, each file once.
+fn write_bundle_media(
+    zip: &mut ZipWriter<File>,
+    cards: &[Card],
+    folder: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let options = FileOptions::default();
+    // Fields may share files
+    let mut written = HashSet::new();
+    for field in cards.iter().flat_map(|card| [&card.front, &card.back]) {
+        for (media_folder, file_name) in
+            [("audio", &field.audio_path), ("images", &field.image_path)]
+        {
+            let Some(file_name) = file_name else {
+                continue;
+            };
+            // Old cards store full paths, the bundle only gets the file name
+            let Some(entry_name) = Path::new(file_name).file_name() else {
+                continue;
+            };
+            let name = format!("{}/{}", media_folder, entry_name.to_string_lossy());
+            if written.insert(name.clone()) {
+                zip.start_file(name, options)?;
+                io::copy(
+                    &mut File::open(folder.join(media_folder).join(file_name))?,
+                    zip,
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Zips the deck files in the folder together with its audio and images folders.
+pub fn folder_backup(folder: &Path, path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(folder)? {
+        let file = entry?.path();
+        if file
+            .extension()
+            .is_some_and(|extension| extension == "sdeck")
+        {
+            files.push((
+                file.file_name().unwrap().to_string_lossy().to_string(),
+                file,
+            ));
+        }
+    }
+    for media_folder in ["audio", "images"] {
+        let media_path = folder.join(media_folder);
+        if !media_path.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(media_path)? {
+            let file = entry?.path();
+            if file.is_file() {
+                let name = file.file_name().unwrap().to_string_lossy();
+                files.push((format!("{}/{}", media_folder, name), file));
+            }
+        }
+    }
+
+    let mut zip = ZipWriter::new(File::create(path)?);
+    let options = FileOptions::default();
+    for (name, file) in files {
+        zip.start_file(name, options)?;
+        io::copy(&mut File::open(file)?, &mut zip)?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+/// Writes the deck as a printable handout: fronts on the left, backs on the right.
+/// Built-in PDF fonts are used, so only latin text is rendered correctly.
+pub fn deck_to_pdf(deck: &Deck, path: &Path) -> Result<(), Box<dyn Error>> {
+    let (document, page, layer) = PdfDocument::new(
+        deck.name.clone(),
+        Mm(PAGE_WIDTH),
+        Mm(PAGE_HEIGHT),
+        "Cards".to_string(),
+    );
+    let font = document.add_builtin_font(BuiltinFont::Helvetica)?;
+    let title_font = document.add_builtin_font(BuiltinFont::HelveticaBold)?;
+
+    let mut layer = document.get_page(page).get_layer(layer);
+    layer.use_text(
+        deck.name.clone(),
+        TITLE_FONT_SIZE,
+        Mm(MARGIN),
+        Mm(PAGE_HEIGHT - MARGIN),
+        &title_font,
+    );
+    let mut y = PAGE_HEIGHT - MARGIN - 2.0 * LINE_HEIGHT;
+
+    for card in &deck.cards {
+        let front = wrap(&card.front.text, COLUMN_WIDTH);
+        let back = wrap(&card.back.text, COLUMN_WIDTH);
+
+        for line in 0..front.len().max(back.len()) {
+            if y < MARGIN {
+                let (page, new_layer) =
+                    document.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Cards".to_string());
+                layer = document.get_page(page).get_layer(new_layer);
+                y = PAGE_HEIGHT - MARGIN;
+            }
+
+            if let Some(text) = front.get(line) {
+                layer.use_text(text.clone(), FONT_SIZE, Mm(MARGIN), Mm(y), &font);
+            }
+            if let Some(text) = back.get(line) {
+                layer.use_text(text.clone(), FONT_SIZE, Mm(PAGE_WIDTH / 2.0), Mm(y), &font);
+            }
+            y -= LINE_HEIGHT;
+        }
+
+        // Space between cards
+        y -= LINE_HEIGHT;
+    }
+
+    document.save(&mut BufWriter::new(File::create(path)?))?;
+    Ok(())
+}
+
+/// Splits text into lines of at most `width` characters, breaking on spaces when possible.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            // Words that are longer than a line are broken into pieces
+            for piece in word.chars().collect::<Vec<char>>().chunks(width) {
+                let piece: String = piece.iter().collect();
+                if !line.is_empty() && line.chars().count() + 1 + piece.chars().count() > width {
+                    lines.push(std::mem::take(&mut line));
+                }
+                if !line.is_empty() {
+                    line.push(' ');
+                }
+                line.push_str(&piece);
+            }
+        }
+        lines.push(line);
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smart_learner_core::{
+        card::{IntervalModifiers, Side},
+        result::Result,
+    };
+
+    #[test]
+    fn clean_deck_forgets_reviews_but_keeps_tags_and_media() {
+        let mut deck = Deck::new("Shared".to_string());
+        let mut card = Card::new(
+            Field {
+                text: "cat".to_string(),
+                audio_path: Some("cat.mp3".to_string()),
+                image_path: Some("cat.png".to_string()),
+                image_after_answer: true,
+            },
+            Field {
+                text: "gato".to_string(),
+                audio_path: None,
+                image_path: None,
+                image_after_answer: false,
+            },
+        );
+        card.tags = vec!["animals".to_string()];
+        let modifiers = IntervalModifiers {
+            hard_multiplier: 1.2,
+            easy_multiplier: 1.3,
+            wrong_interval: 1,
+            again_interval_mins: 1,
+            graduating_interval: 1,
+            easy_interval: 1,
+            learning_steps: Vec::new(),
+        };
+        card.review(Side::Forward, Result::Wrong, &modifiers);
+        card.review(Side::Forward, Result::Easy, &modifiers);
+        card.suspended = true;
+        deck.cards.push(card);
+
+        let clean = clean_deck(&deck);
+        let card = &clean.cards[0];
+        assert!(card.history.is_empty());
+        assert_eq!(card.lapses, 0);
+        assert!(!card.suspended);
+        assert_eq!(card.schedule.repetitions, 0);
+        assert_eq!(card.schedule.repeat_in, 0);
+        assert!(card.due_side().is_some());
+        assert_eq!(card.tags, deck.cards[0].tags);
+        assert_eq!(card.front.audio_path.as_deref(), Some("cat.mp3"));
+        assert_eq!(card.front.image_path.as_deref(), Some("cat.png"));
+        assert!(card.front.image_after_answer);
+        assert_eq!(card.back.text, "gato");
+    }
+
+    /// Counts page objects in a PDF, `/Type /Pages` is the page tree and isn't counted.
+    fn pdf_page_count(pdf: &[u8]) -> usize {
+        let text = String::from_utf8_lossy(pdf);
+        text.match_indices("/Type")
+            .filter(|(index, _)| {
+                let rest = text[index + "/Type".len()..].trim_start();
+                rest.starts_with("/Page") && !rest.starts_with("/Pages")
+            })
+            .count()
+    }
+
+    #[test]
+    fn pdf_has_a_page_for_every_screenful_of_cards() {
+        let folder = std::env::temp_dir().join("smart-learner-test-pdf");
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+
+        // The first page fits 26 one line cards under the title, the next ones 27
+        for (cards, pages) in [(1, 1), (27, 2), (60, 3)] {
+            let mut deck = Deck::new("Printed".to_string());
+            for index in 0..cards {
+                let field = Field {
+                    text: format!("card {}", index),
+                    audio_path: None,
+                    image_path: None,
+                    image_after_answer: false,
+                };
+                deck.cards.push(Card::new(field.clone(), field));
+            }
+
+            let path = folder.join(format!("{}.pdf", cards));
+            deck_to_pdf(&deck, &path).unwrap();
+            let pdf = fs::read(&path).unwrap();
+            assert!(pdf.starts_with(b"%PDF-"));
+            assert_eq!(pdf_page_count(&pdf), pages);
+        }
+    }
+
+    #[test]
+    fn bundle_media_is_stored_by_file_name() {
+        let folder = std::env::temp_dir().join("smart-learner-test-bundle-media");
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(folder.join("recordings")).unwrap();
+        // Old cards store the full path
+        let audio_path = folder.join("recordings").join("cat.mp3");
+        fs::write(&audio_path, "audio").unwrap();
+        let field = Field {
+            text: "cat".to_string(),
+            audio_path: Some(audio_path.to_string_lossy().to_string()),
+            image_path: None,
+            image_after_answer: false,
+        };
+        let card = Card::new(field.clone(), field);
+
+        let path = folder.join("cat.zip");
+        card_bundle(&card, &folder, &path).unwrap();
+        let archive = zip::ZipArchive::new(File::open(&path).unwrap()).unwrap();
+        let mut names: Vec<&str> = archive.file_names().collect();
+        names.sort_unstable();
+        assert_eq!(names, vec!["audio/cat.mp3", BUNDLE_CARD_FILE]);
+    }
+}
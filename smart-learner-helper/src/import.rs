@@ -0,0 +1,110 @@
+pub mod anki;
+
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{self, Read},
+    path::Path,
+};
+
+use smart_learner_core::{card::Card, field::Field};
+use zip::ZipArchive;
+
+use crate::export::BUNDLE_CARD_FILE;
+
+/// Reads cards from a CSV file with front and back columns.
+/// Quoted fields may contain commas and line breaks, a `front,back` header row is skipped.
+pub fn cards_from_csv(path: &Path) -> Result<Vec<Card>, Box<dyn Error>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_path(path)?;
+
+    let mut cards = Vec::new();
+    for (row, record) in reader.records().enumerate() {
+        let record = record?;
+        let front = record.get(0).unwrap_or_default();
+        let back = record.get(1).unwrap_or_default();
+        if row == 0 && front == "front" && back == "back" {
+            continue;
+        }
+
+        cards.push(Card::new(
+            Field {
+                text: front.to_string(),
+                audio_path: None,
+                image_path: None,
+                image_after_answer: false,
+            },
+            Field {
+                text: back.to_string(),
+                audio_path: None,
+                image_path: None,
+                image_after_answer: false,
+            },
+        ));
+    }
+    Ok(cards)
+}
+
+/// Makes cards from tab separated lines, the first column is the front and the second the back.
+/// Blank lines and lines without a tab are skipped.
+pub fn cards_from_tsv(text: &str) -> Vec<Card> {
+    text.lines()
+        .filter_map(|line| {
+            let mut columns = line.split('\t');
+            let front = columns.next()?;
+            let back = columns.next()?;
+            Some(Card::new(
+                Field {
+                    text: front.to_string(),
+                    audio_path: None,
+                    image_path: None,
+                    image_after_answer: false,
+                },
+                Field {
+                    text: back.to_string(),
+                    audio_path: None,
+                    image_path: None,
+                    image_after_answer: false,
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Reads a card written by `export::card_bundle`.
+/// Its media is unpacked into a temporary folder and the fields hold the full paths,
+/// so the caller has to copy the files.
+pub fn card_from_bundle(path: &Path) -> Result<Card, Box<dyn Error>> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let mut json = String::new();
+    archive
+        .by_name(BUNDLE_CARD_FILE)?
+        .read_to_string(&mut json)?;
+    let mut card: Card = serde_json::from_str(&json)?;
+
+    let media_folder = std::env::temp_dir().join("smart-learner-bundle");
+    fs::create_dir_all(&media_folder)?;
+    for field in [&mut card.front, &mut card.back] {
+        for (folder, file_name) in [
+            ("audio", &mut field.audio_path),
+            ("images", &mut field.image_path),
+        ] {
+            let Some(name) = file_name.take() else {
+                continue;
+            };
+            // Only the file name is used, so the bundle can't write outside the folder
+            let Some(unpacked_name) = Path::new(&name).file_name() else {
+                continue;
+            };
+            let unpacked = media_folder.join(unpacked_name);
+            io::copy(
+                &mut archive.by_name(&format!("{}/{}", folder, name))?,
+                &mut File::create(&unpacked)?,
+            )?;
+            *file_name = Some(unpacked.to_string_lossy().to_string());
+        }
+    }
+    Ok(card)
+}
@@ -0,0 +1,164 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::{self, File},
+    io,
+    path::Path,
+};
+
+use rusqlite::{Connection, OpenFlags};
+use serde_derive::Deserialize;
+use smart_learner_core::{card::Card, deck::Deck, field::Field};
+use zip::ZipArchive;
+
+/// Separates fields of a note in the collection.
+const FIELD_SEPARATOR: char = '\x1f';
+
+/// Note type, only the number of card templates is needed.
+#[derive(Deserialize)]
+struct Model {
+    tmpls: Vec<Template>,
+}
+
+#[derive(Deserialize)]
+struct Template {}
+
+/// Reads an Anki package into a deck named after the file.
+/// The first field of a note is the front and the second one is the back,
+/// note types with two templates (like "Basic (and reversed card)") give bidirectional cards.
+/// Referenced media is unpacked into a temporary folder and `audio_path` holds the full path,
+/// so the caller has to copy it. Only packages with `collection.anki2` are supported.
+pub fn deck_from_apkg(path: &Path) -> Result<Deck, Box<dyn Error>> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let media_folder = std::env::temp_dir().join("smart-learner-anki");
+    fs::create_dir_all(&media_folder)?;
+
+    // The collection is a SQLite database, which has to be a file to be opened
+    let collection_path = media_folder.join("collection.anki2");
+    io::copy(
+        &mut archive.by_name("collection.anki2")?,
+        &mut File::create(&collection_path)?,
+    )?;
+
+    // Media files are stored by number, the "media" file maps numbers to names
+    let media: HashMap<String, String> = serde_json::from_reader(archive.by_name("media")?)?;
+    let media_numbers: HashMap<String, String> = media
+        .into_iter()
+        .map(|(number, name)| (name, number))
+        .collect();
+
+    let collection =
+        Connection::open_with_flags(&collection_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let models: String = collection.query_row("SELECT models FROM col", [], |row| row.get(0))?;
+    let models: HashMap<String, Model> = serde_json::from_str(&models)?;
+
+    let mut statement = collection.prepare("SELECT mid, flds FROM notes ORDER BY id")?;
+    let notes = statement.query_map([], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+    })?;
+
+    let name = path.file_stem().unwrap().to_string_lossy().to_string();
+    let mut deck = Deck::new(name);
+    for note in notes {
+        let (model_id, fields) = note?;
+        let mut fields = fields.split(FIELD_SEPARATOR);
+        let front = unpack_field(
+            fields.next().unwrap_or_default(),
+            &mut archive,
+            &media_numbers,
+            &media_folder,
+        )?;
+        let back = unpack_field(
+            fields.next().unwrap_or_default(),
+            &mut archive,
+            &media_numbers,
+            &media_folder,
+        )?;
+
+        let mut card = Card::new(front, back);
+        card.bidirectional = models
+            .get(&model_id.to_string())
+            .is_some_and(|model| model.tmpls.len() >= 2);
+        deck.add_card(card);
+    }
+
+    drop(statement);
+    collection.close().map_err(|(_, error)| error)?;
+    fs::remove_file(collection_path)?;
+    Ok(deck)
+}
+
+/// Turns a note field into a card field, unpacking its sound into `media_folder`.
+fn unpack_field(
+    text: &str,
+    archive: &mut ZipArchive<File>,
+    media_numbers: &HashMap<String, String>,
+    media_folder: &Path,
+) -> Result<Field, Box<dyn Error>> {
+    let mut audio_path = None;
+    if let Some(name) = sound_file(text) {
+        if let Some(number) = media_numbers.get(name) {
+            let file_path = media_folder.join(name);
+            io::copy(
+                &mut archive.by_name(number)?,
+                &mut File::create(&file_path)?,
+            )?;
+            audio_path = Some(file_path.to_string_lossy().to_string());
+        }
+    }
+
+    Ok(Field {
+        text: plain_text(text),
+        audio_path,
+        image_path: None,
+        image_after_answer: false,
+    })
+}
+
+/// Name of the first file in a `[sound:...]` tag.
+fn sound_file(text: &str) -> Option<&str> {
+    let start = text.find("[sound:")? + "[sound:".len();
+    let length = text[start..].find(']')?;
+    Some(&text[start..start + length])
+}
+
+/// Removes HTML and sound tags from a field, keeping line breaks.
+fn plain_text(text: &str) -> String {
+    let text = text
+        .replace("<br>", "\n")
+        .replace("<br/>", "\n")
+        .replace("<br />", "\n")
+        .replace("<div>", "\n");
+
+    let mut result = String::new();
+    let mut rest = text.as_str();
+    while let Some(start) = rest.find(['<', '[']) {
+        result.push_str(&rest[..start]);
+        let end = match &rest[start..] {
+            tag if tag.starts_with('<') => tag.find('>'),
+            tag if tag.starts_with("[sound:") => tag.find(']'),
+            _ => {
+                result.push('[');
+                rest = &rest[start + 1..];
+                continue;
+            }
+        };
+        match end {
+            Some(end) => rest = &rest[start + end + 1..],
+            None => {
+                rest = &rest[start..];
+                break;
+            }
+        }
+    }
+    result.push_str(rest);
+
+    result
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+        .trim()
+        .to_string()
+}
@@ -1,3 +1,8 @@
 pub mod config;
 pub mod app;
-pub mod data;
\ No newline at end of file
+pub mod data;
+pub mod export;
+pub mod import;
+pub mod session;
+pub mod tts;
+pub mod watcher;
\ No newline at end of file
@@ -0,0 +1,72 @@
+use std::time::{Duration, Instant};
+
+/// Measures time spent studying in a revision session.
+/// Of a pause without input only the first `idle_timeout` is counted, so walking away
+/// doesn't add to the time.
+pub struct SessionTimer {
+    /// Time counted until `last_activity`.
+    counted: Duration,
+    last_activity: Instant,
+}
+
+impl SessionTimer {
+    pub fn start(now: Instant) -> Self {
+        Self {
+            counted: Duration::ZERO,
+            last_activity: now,
+        }
+    }
+
+    /// Records input at `now`, an `idle_timeout` of zero counts every pause fully.
+    pub fn activity(&mut self, now: Instant, idle_timeout: Duration) {
+        self.counted = self.elapsed(now, idle_timeout);
+        self.last_activity = now;
+    }
+
+    /// Study time from the start of the session to `now`.
+    pub fn elapsed(&self, now: Instant, idle_timeout: Duration) -> Duration {
+        let mut pause = now.saturating_duration_since(self.last_activity);
+        if !idle_timeout.is_zero() {
+            pause = pause.min(idle_timeout);
+        }
+        self.counted + pause
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn active_time_is_counted() {
+        let start = Instant::now();
+        let mut timer = SessionTimer::start(start);
+        for seconds in [10, 25, 40] {
+            timer.activity(start + Duration::from_secs(seconds), TIMEOUT);
+        }
+        let now = start + Duration::from_secs(45);
+        assert_eq!(timer.elapsed(now, TIMEOUT), Duration::from_secs(45));
+    }
+
+    #[test]
+    fn idle_time_past_timeout_is_not_counted() {
+        let start = Instant::now();
+        let mut timer = SessionTimer::start(start);
+        timer.activity(start + Duration::from_secs(10), TIMEOUT);
+
+        // Away for ten minutes, only the timeout counts
+        let back = start + Duration::from_secs(610);
+        assert_eq!(timer.elapsed(back, TIMEOUT), Duration::from_secs(70));
+        timer.activity(back, TIMEOUT);
+        let now = back + Duration::from_secs(5);
+        assert_eq!(timer.elapsed(now, TIMEOUT), Duration::from_secs(75));
+
+        assert_eq!(timer.elapsed(now, Duration::ZERO), Duration::from_secs(75));
+        assert_eq!(
+            SessionTimer::start(start).elapsed(now, Duration::ZERO),
+            Duration::from_secs(615)
+        );
+    }
+}
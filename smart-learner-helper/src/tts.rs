@@ -0,0 +1,33 @@
+use std::{
+    io::ErrorKind,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+/// Speech synthesizers that are tried in order, they take the same arguments.
+const ESPEAK_PROGRAMS: [&str; 2] = ["espeak-ng", "espeak"];
+
+/// Speaks the text in the language into a wav file,
+/// using the first espeak that is installed.
+pub fn synthesize(text: &str, lang: &str, output: &Path) -> Result<(), String> {
+    for program in ESPEAK_PROGRAMS {
+        let status = Command::new(program)
+            .arg("-v")
+            .arg(lang)
+            .arg("-w")
+            .arg(output)
+            .arg(text)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+
+        match status {
+            Ok(status) if status.success() => return Ok(()),
+            Ok(status) => return Err(format!("{} failed: {}", program, status)),
+            Err(error) if error.kind() == ErrorKind::NotFound => continue,
+            Err(error) => return Err(format!("Can't run {}: {}", program, error)),
+        }
+    }
+
+    Err("No text-to-speech program found, install espeak-ng to generate audio.".to_string())
+}